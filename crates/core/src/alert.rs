@@ -1,47 +1,206 @@
-use log::info;
+use chrono::Utc;
+use mongodb::Collection;
+use mongodb::bson::Document;
 use teloxide::{prelude::*, types::ParseMode};
+use tokio::sync::Mutex;
 
-use crate::mongo::Website;
+use crate::callback::alert_keyboard;
+use crate::digest::Change;
+use crate::duration::format_duration;
+use crate::format::format_changes;
+use crate::html::{MAX_MESSAGE_LEN, split_message};
+use crate::i18n::{Language, t};
+use crate::mongo::{ChatSettings, Website, deactivate_chat};
+use crate::notify::{Event, Notifier};
+use crate::outbox::Outbox;
+use crate::template::{AlertContext, Templates};
 
-pub async fn alert_users(bot: Bot, changed_webs: &[Website]) {
-    for website in changed_webs {
-        let tele_id = website.telegram_id.parse::<i64>().unwrap();
-        let chat_id = ChatId(tele_id);
-
-        let message = process(&website.url, website.status);
+/// Delivers events as Telegram messages, paced by an [`Outbox`]. Chats found
+/// to be unreachable are deactivated once the sweep is done.
+pub struct TelegramNotifier {
+    outbox: Mutex<Outbox>,
+    collection: Collection<Document>,
+}
 
-        if let Err(e) = bot
-            .send_message(chat_id, message)
-            .parse_mode(ParseMode::Html)
-            .await
-        {
-            info!("Failed to send message to {chat_id}: {e}");
+impl TelegramNotifier {
+    pub fn new(bot: Bot, collection: Collection<Document>) -> Self {
+        TelegramNotifier {
+            outbox: Mutex::new(Outbox::new(bot)),
+            collection,
         }
     }
 }
 
-pub fn process(site: &str, code: i32) -> String {
-    let mut output = format!("Site: {site}\n\n");
+#[async_trait::async_trait]
+impl Notifier for TelegramNotifier {
+    fn digests(&self) -> bool {
+        true
+    }
+
+    async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+        let mut outbox = self.outbox.lock().await;
+        let changed: Vec<&Website> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Changed(website) => Some(website),
+                _ => None,
+            })
+            .collect();
 
-    match code {
-        0 | 1 => output += "Hoppla! We faced an error trying to reach the site! 🤒",
-        200..=299 => {
-            output += &format!(
-                "Joohoo! It's live and kicking! 🙂\n\nStatus: <a href='https://httpstatuses.com/{code}'>{code}</a>"
-            )
+        let mut delivered = alert_users(&mut outbox, chat, &changed).await;
+        for event in events {
+            delivered &= match event {
+                Event::Changed(_) => true,
+                Event::Reminder(website) => remind_users(&mut outbox, website, chat).await,
+                Event::MaintenanceOver(website) => {
+                    summarise_maintenance(&mut outbox, website, chat).await
+                }
+                Event::Escalated { website, target } => {
+                    escalate(&mut outbox, ChatId(*target), website, chat).await
+                }
+                Event::Digest(changes) => send_digest(&mut outbox, chat, changes).await,
+            };
         }
-        400..=499 => {
-            output += &format!(
-                "Erm! Did I do something wrong? 🤔\n\nStatus: <a href='https://httpstatuses.com/{code}'>{code}</a>"
-            )
+        delivered
+    }
+
+    // Start the next sweep with a fresh outbox
+    async fn finish(&self) {
+        let mut outbox = self.outbox.lock().await;
+        let bot = outbox.bot().clone();
+        let outbox = std::mem::replace(&mut *outbox, Outbox::new(bot));
+        log::info!("{}", outbox.stats());
+
+        for chat in outbox.unreachable() {
+            match deactivate_chat(&self.collection, chat.0).await {
+                Ok(()) => log::info!("Chat {chat} is unreachable, no longer checking its sites"),
+                Err(e) => log::error!("Error deactivating chat {chat}: {e}"),
+            }
         }
-        500..=599 => {
-            output += &format!(
-                "Schade! It's down or inaccessible to me! 😟\n\nStatus: <a href='https://httpstatuses.com/{code}'>{code}</a>"
-            )
+    }
+}
+
+/// Alert a chat about its changed sites, keeping quiet hours silent. Several
+/// changes in one sweep are sent as a single table.
+async fn alert_users(outbox: &mut Outbox, chat: &ChatSettings, changed_webs: &[&Website]) -> bool {
+    match changed_webs {
+        [] => true,
+        [website] => {
+            let message = render_alert(chat, website);
+            send_alert(outbox, website, chat, message).await
+        }
+        _ => {
+            let changes: Vec<Change> = changed_webs.iter().map(|w| Change::from(*w)).collect();
+            let language = chat.language.unwrap_or_default();
+            let title = t!(language, "alert.grouped", count = changes.len());
+            send_grouped(outbox, chat, format_changes(language, &title, &changes)).await
         }
-        _ => output += "Something is fishy! 🐟",
     }
+}
+
+/// Send the changes a chat in digest mode collected since its last digest.
+async fn send_digest(outbox: &mut Outbox, chat: &ChatSettings, changes: &[Change]) -> bool {
+    let language = chat.language.unwrap_or_default();
+    let title = t!(language, "alert.digest", count = changes.len());
+    send_grouped(outbox, chat, format_changes(language, &title, changes)).await
+}
+
+/// Repeat the alert for an incident nobody has acknowledged yet.
+async fn remind_users(outbox: &mut Outbox, website: &Website, chat: &ChatSettings) -> bool {
+    let message = with_alert("alert.reminder", chat, website);
+    send_alert(outbox, website, chat, message).await
+}
+
+/// Report a site that is still down after its maintenance window ended.
+async fn summarise_maintenance(
+    outbox: &mut Outbox,
+    website: &Website,
+    chat: &ChatSettings,
+) -> bool {
+    let message = with_alert("alert.maintenance_over", chat, website);
+    send_alert(outbox, website, chat, message).await
+}
+
+/// Forward an unacknowledged incident to the chat's escalation target, in
+/// the wording of the chat it came from.
+async fn escalate(
+    outbox: &mut Outbox,
+    target: ChatId,
+    website: &Website,
+    chat: &ChatSettings,
+) -> bool {
+    let message = with_alert("alert.escalated", chat, website);
+    let request = outbox
+        .bot()
+        .send_message(target, message)
+        .parse_mode(ParseMode::Html);
+    outbox.send(target, request).await
+}
+
+// A headline saying how long the site has been down, followed by the alert
+fn with_alert(key: &str, chat: &ChatSettings, website: &Website) -> String {
+    let language = chat.language.unwrap_or_default();
+    let down = match &website.incident {
+        Some(incident) => t!(
+            language,
+            "alert.down_for",
+            duration = format_duration(Utc::now() - incident.started_at)
+        ),
+        None => t!(language, "alert.down"),
+    };
+    format!(
+        "{}\n\n{}",
+        t!(language, key, down = down),
+        render_alert(chat, website)
+    )
+}
+
+async fn send_alert(
+    outbox: &mut Outbox,
+    website: &Website,
+    chat: &ChatSettings,
+    message: String,
+) -> bool {
+    let chat_id = ChatId(website.chat_id);
+    let mut request = outbox
+        .bot()
+        .send_message(chat_id, message)
+        .parse_mode(ParseMode::Html)
+        .disable_notification(chat.is_quiet(Utc::now()));
+    if let Some(id) = website.id {
+        request = request.reply_markup(alert_keyboard(id, chat.language.unwrap_or_default()));
+    }
+    outbox.send(chat_id, request).await
+}
+
+// Grouped messages cover several sites, so they carry no action buttons.
+// A long table is sent in several parts.
+async fn send_grouped(outbox: &mut Outbox, chat: &ChatSettings, message: String) -> bool {
+    let chat_id = ChatId(chat.chat_id);
+    for part in split_message(&message, MAX_MESSAGE_LEN) {
+        let request = outbox
+            .bot()
+            .send_message(chat_id, part)
+            .parse_mode(ParseMode::Html)
+            .disable_notification(chat.is_quiet(Utc::now()));
+        if !outbox.send(chat_id, request).await {
+            return false;
+        }
+    }
+    true
+}
+
+/// An alert in the built-in wording, for replies outside any chat's
+/// settings.
+pub fn process(language: Language, site: &str, code: i32) -> String {
+    Templates::default().render(&AlertContext::new(site, code), language)
+}
 
-    output
+/// An alert in the chat's own wording.
+pub fn render_alert(chat: &ChatSettings, website: &Website) -> String {
+    let language = chat.language.unwrap_or_default();
+    chat.templates.render(
+        &AlertContext::from_website(website, Utc::now(), language),
+        language,
+    )
 }
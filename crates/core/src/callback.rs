@@ -0,0 +1,104 @@
+use mongodb::bson::oid::ObjectId;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::i18n::{Language, tr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Recheck,
+    Mute,
+    Acknowledge,
+    Untrack,
+}
+
+impl Action {
+    fn tag(self) -> &'static str {
+        match self {
+            Action::Recheck => "recheck",
+            Action::Mute => "mute",
+            Action::Acknowledge => "ack",
+            Action::Untrack => "untrack",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "recheck" => Some(Action::Recheck),
+            "mute" => Some(Action::Mute),
+            "ack" => Some(Action::Acknowledge),
+            "untrack" => Some(Action::Untrack),
+            _ => None,
+        }
+    }
+}
+
+/// Payload of an alert button, encoded as `<action>:<website id>` to stay
+/// well within Telegram's 64-byte callback data limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Callback {
+    pub action: Action,
+    pub site: ObjectId,
+}
+
+impl Callback {
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.action.tag(), self.site.to_hex())
+    }
+
+    pub fn decode(data: &str) -> Option<Self> {
+        let (tag, id) = data.split_once(':')?;
+        Some(Callback {
+            action: Action::from_tag(tag)?,
+            site: ObjectId::parse_str(id).ok()?,
+        })
+    }
+}
+
+pub fn alert_keyboard(site: ObjectId, language: Language) -> InlineKeyboardMarkup {
+    let button = |key: &str, action| {
+        InlineKeyboardButton::callback(tr(language, key), Callback { action, site }.encode())
+    };
+    InlineKeyboardMarkup::new([
+        [
+            button("button.recheck", Action::Recheck),
+            button("button.mute", Action::Mute),
+        ],
+        [
+            button("button.ack", Action::Acknowledge),
+            button("button.untrack", Action::Untrack),
+        ],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let site = ObjectId::new();
+        for action in [
+            Action::Recheck,
+            Action::Mute,
+            Action::Acknowledge,
+            Action::Untrack,
+        ] {
+            let data = Callback { action, site }.encode();
+            assert!(data.len() <= 64);
+            assert_eq!(Callback::decode(&data), Some(Callback { action, site }));
+        }
+    }
+
+    #[test]
+    fn test_decode_unknown_action() {
+        let data = format!("reboot:{}", ObjectId::new().to_hex());
+        assert_eq!(Callback::decode(&data), None);
+    }
+
+    #[test]
+    fn test_decode_invalid_id() {
+        assert_eq!(Callback::decode("mute:not-an-id"), None);
+        assert_eq!(Callback::decode("mute"), None);
+        assert_eq!(Callback::decode(""), None);
+    }
+}
@@ -0,0 +1,462 @@
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::alert::render_alert;
+use crate::html::strip_tags;
+use crate::http::FailureKind;
+use crate::i18n::{Language, t};
+use crate::incident::Transition;
+use crate::matrix::is_valid_room_id;
+use crate::mongo::{ChatSettings, Website};
+use crate::notify::{Event, Notifier};
+use crate::webhook::{is_http_url, is_valid_webhook_url, pinned_client};
+use crate::{discord, gotify, matrix, ntfy, slack};
+
+pub const MAX_CHANNELS: usize = 5;
+
+// Slack and Discord webhooks live on these hosts only, so anything else is
+// refused rather than posted to
+const SLACK_HOSTS: [&str; 1] = ["hooks.slack.com"];
+const DISCORD_HOSTS: [&str; 2] = ["discord.com", "discordapp.com"];
+
+/// A chat app that receives the chat's alerts in addition to Telegram.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Channel {
+    /// A Slack incoming webhook
+    Slack { url: String },
+    /// A Discord channel webhook
+    Discord { url: String },
+    /// An ntfy topic URL, with an access token if the topic is protected
+    Ntfy {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    /// A Gotify server and application token
+    Gotify { url: String, token: String },
+    /// A Matrix room, posted to with the access token of a bot account
+    Matrix {
+        homeserver: String,
+        room: String,
+        token: String,
+    },
+}
+
+/// Why the arguments of `/connect` were refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelError {
+    MissingKind,
+    UnknownKind(String),
+    /// Slack and Discord take just their webhook URL
+    WebhookArguments(String),
+    NtfyArguments,
+    GotifyArguments,
+    MatrixArguments,
+    /// Not an https URL on the service's own host
+    ServiceUrl {
+        url: String,
+        host: &'static str,
+    },
+    HttpUrl(String),
+}
+
+impl ChannelError {
+    pub fn message(&self, language: Language) -> String {
+        match self {
+            ChannelError::MissingKind => t!(language, "connect.missing_kind"),
+            ChannelError::UnknownKind(kind) => t!(language, "connect.unknown_kind", kind = kind),
+            ChannelError::WebhookArguments(kind) => {
+                t!(language, "connect.webhook_arguments", kind = kind)
+            }
+            ChannelError::NtfyArguments => t!(language, "connect.ntfy_arguments"),
+            ChannelError::GotifyArguments => t!(language, "connect.gotify_arguments"),
+            ChannelError::MatrixArguments => t!(language, "connect.matrix_arguments"),
+            ChannelError::ServiceUrl { url, host } => {
+                t!(language, "connect.service_url", url = url, host = host)
+            }
+            ChannelError::HttpUrl(url) => t!(language, "connect.http_url", url = url),
+        }
+    }
+}
+
+impl Channel {
+    /// Parse the arguments of `/connect`, e.g. `slack https://hooks.slack.com/services/...`.
+    /// ntfy, Gotify and Matrix servers are self-hosted, so they may use plain
+    /// http; whether they are public is checked by [`Channel::is_public`].
+    pub fn parse(spec: &str) -> Result<Self, ChannelError> {
+        let tokens: Vec<&str> = spec.split_whitespace().collect();
+        let Some((kind, args)) = tokens.split_first() else {
+            return Err(ChannelError::MissingKind);
+        };
+
+        match (kind.to_lowercase().as_str(), args) {
+            ("slack", [url]) => Ok(Channel::Slack {
+                url: service_url(url, &SLACK_HOSTS)?,
+            }),
+            ("discord", [url]) => Ok(Channel::Discord {
+                url: service_url(url, &DISCORD_HOSTS)?,
+            }),
+            ("ntfy", [url]) => Ok(Channel::Ntfy {
+                url: http_url(url)?,
+                token: None,
+            }),
+            ("ntfy", [url, token]) => Ok(Channel::Ntfy {
+                url: http_url(url)?,
+                token: Some(token.to_string()),
+            }),
+            ("gotify", [url, token]) => Ok(Channel::Gotify {
+                url: http_url(url)?,
+                token: token.to_string(),
+            }),
+            ("matrix", [homeserver, room, token]) if is_valid_room_id(room) => {
+                Ok(Channel::Matrix {
+                    homeserver: http_url(homeserver)?,
+                    room: room.to_string(),
+                    token: token.to_string(),
+                })
+            }
+            ("slack" | "discord", _) => Err(ChannelError::WebhookArguments(kind.to_string())),
+            ("ntfy", _) => Err(ChannelError::NtfyArguments),
+            ("gotify", _) => Err(ChannelError::GotifyArguments),
+            ("matrix", _) => Err(ChannelError::MatrixArguments),
+            _ => Err(ChannelError::UnknownKind(kind.to_string())),
+        }
+    }
+
+    /// The server the channel posts to, for kinds whose server the chat
+    /// chooses; Slack and Discord only post to their own hosts.
+    fn server(&self) -> Option<&str> {
+        match self {
+            Channel::Ntfy { url, .. } | Channel::Gotify { url, .. } => Some(url),
+            Channel::Matrix { homeserver, .. } => Some(homeserver),
+            Channel::Slack { .. } | Channel::Discord { .. } => None,
+        }
+    }
+
+    /// Whether the channel's server resolves to public addresses only, the
+    /// same check as for webhooks, so a chat cannot make the bot post into
+    /// the network it runs in.
+    pub async fn is_public(&self) -> bool {
+        match self.server() {
+            Some(url) => is_valid_webhook_url(url).await,
+            None => true,
+        }
+    }
+
+    /// Name and host only; URLs may carry a secret in their path.
+    pub fn describe(&self) -> String {
+        let (kind, url) = match self {
+            Channel::Slack { url } => ("Slack", url),
+            Channel::Discord { url } => ("Discord", url),
+            Channel::Ntfy { url, .. } => ("ntfy", url),
+            Channel::Gotify { url, .. } => ("Gotify", url),
+            Channel::Matrix { homeserver, .. } => ("Matrix", homeserver),
+        };
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        format!("{kind} ({host})")
+    }
+
+    fn request(&self, client: &Client, chat: &ChatSettings, website: &Website) -> RequestBuilder {
+        let language = chat.language.unwrap_or_default();
+        match self {
+            Channel::Slack { url } => client.post(url).json(&slack::payload(website, language)),
+            Channel::Discord { url } => client.post(url).json(&discord::payload(website, language)),
+            Channel::Ntfy { url, token } => {
+                ntfy::request(client, url, token.as_deref(), website, language)
+            }
+            Channel::Gotify { url, token } => {
+                gotify::request(client, url, token, website, language)
+            }
+            Channel::Matrix {
+                homeserver,
+                room,
+                token,
+            } => matrix::request(
+                client,
+                homeserver,
+                room,
+                token,
+                &render_alert(chat, website),
+            ),
+        }
+    }
+}
+
+fn service_url(input: &str, hosts: &[&'static str]) -> Result<String, ChannelError> {
+    let on_host = url::Url::parse(input).is_ok_and(|url| {
+        url.scheme() == "https"
+            && url.port().is_none()
+            && url.host_str().is_some_and(|host| hosts.contains(&host))
+    });
+    if on_host {
+        Ok(input.to_string())
+    } else {
+        Err(ChannelError::ServiceUrl {
+            url: input.to_string(),
+            host: hosts[0],
+        })
+    }
+}
+
+fn http_url(input: &str) -> Result<String, ChannelError> {
+    if is_http_url(input) {
+        Ok(input.to_string())
+    } else {
+        Err(ChannelError::HttpUrl(input.to_string()))
+    }
+}
+
+/// One-line summary of a change, e.g. `https://example.com is down`. Chat
+/// apps take plain text, so the message is stripped of its HTML.
+pub(crate) fn headline(website: &Website, language: Language) -> String {
+    let key = match website.transition() {
+        Transition::Down => "channel.down",
+        Transition::Recovery => "channel.recovery",
+        Transition::StatusChange => "channel.changed",
+    };
+    strip_tags(&t!(language, key, site = website.url))
+}
+
+/// The status and what went wrong, e.g. `Status: 0 (was 200), timed out`,
+/// with the status wrapped in `mark` where the app supports emphasis.
+pub(crate) fn status_line(website: &Website, language: Language, mark: &str) -> String {
+    let status = format!("{mark}{}{mark}", website.status);
+    let mut line = match website.previous_status {
+        Some(previous) => t!(
+            language,
+            "channel.status_was",
+            status = status,
+            previous = previous
+        ),
+        None => t!(language, "channel.status", status = status),
+    };
+    if let Some(failure) = website.failure {
+        line.push_str(&format!(", {}", failure.describe(language)));
+    }
+    strip_tags(&line)
+}
+
+/// Red for down, green for recovered, amber for anything else.
+pub(crate) fn colour(transition: Transition) -> u32 {
+    match transition {
+        Transition::Down => 0xd92d20,
+        Transition::Recovery => 0x12b76a,
+        Transition::StatusChange => 0xf79009,
+    }
+}
+
+/// How urgent a change is, on ntfy's scale from 1 (min) to 5 (urgent).
+/// A site that cannot be reached at all outranks one answering with an
+/// error, and recoveries are delivered quietly.
+pub(crate) fn severity(website: &Website) -> u8 {
+    match (website.transition(), website.failure) {
+        (Transition::Recovery, _) => 2,
+        (_, Some(FailureKind::InvalidUrl)) => 3,
+        (Transition::Down, Some(_)) => 5,
+        (Transition::Down, None) => 4,
+        (Transition::StatusChange, _) => 3,
+    }
+}
+
+/// Posts status changes to the chat apps each chat connected.
+pub struct ChannelNotifier {
+    client: Arc<Client>,
+}
+
+impl ChannelNotifier {
+    pub fn new(client: Arc<Client>) -> Self {
+        ChannelNotifier { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for ChannelNotifier {
+    async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+        let mut delivered = true;
+        for event in events {
+            let Event::Changed(website) = event else {
+                continue;
+            };
+            for channel in &chat.channels {
+                // Servers the chat chose are checked again on every post, and
+                // only reached at the addresses checked
+                let client = match channel.server() {
+                    None => Client::clone(&self.client),
+                    Some(url) => match pinned_client(url).await {
+                        Some(client) => client,
+                        None => {
+                            log::info!(
+                                "Not notifying {}: host does not resolve to a public address",
+                                channel.describe()
+                            );
+                            delivered = false;
+                            continue;
+                        }
+                    },
+                };
+                let result = channel.request(&client, chat, website).send().await;
+                if let Err(e) = result.and_then(|response| response.error_for_status()) {
+                    log::info!("Failed to notify {}: {e}", channel.describe());
+                    delivered = false;
+                }
+            }
+        }
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Channel::parse("Slack https://hooks.slack.com/services/T0/B0/x"),
+            Ok(Channel::Slack {
+                url: "https://hooks.slack.com/services/T0/B0/x".to_string()
+            })
+        );
+        assert_eq!(
+            Channel::parse("discord https://discord.com/api/webhooks/1/abc"),
+            Ok(Channel::Discord {
+                url: "https://discord.com/api/webhooks/1/abc".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Channel::parse(""), Err(ChannelError::MissingKind));
+        assert_eq!(
+            Channel::parse("slack"),
+            Err(ChannelError::WebhookArguments("slack".to_string()))
+        );
+        assert_eq!(
+            Channel::parse("slack http://hooks.slack.com/x"),
+            Err(ChannelError::ServiceUrl {
+                url: "http://hooks.slack.com/x".to_string(),
+                host: "hooks.slack.com"
+            })
+        );
+        for url in [
+            "https://hooks.slack.com.evil.org/x",
+            "https://127.0.0.1/x",
+            "https://hooks.slack.com:8443/x",
+        ] {
+            assert!(Channel::parse(&format!("slack {url}")).is_err(), "{url}");
+        }
+        assert!(Channel::parse("discord https://metadata.google.internal/x").is_err());
+        assert!(Channel::parse("discord https://discordapp.com/api/webhooks/1/abc").is_ok());
+        assert_eq!(
+            Channel::parse("irc https://example.com"),
+            Err(ChannelError::UnknownKind("irc".to_string()))
+        );
+        assert_eq!(
+            Channel::parse("gotify https://gotify.example.org"),
+            Err(ChannelError::GotifyArguments)
+        );
+        assert_eq!(
+            Channel::parse("ntfy ntfy.sh/topic"),
+            Err(ChannelError::HttpUrl("ntfy.sh/topic".to_string()))
+        );
+        assert_eq!(
+            Channel::parse("matrix https://matrix.org #ops:matrix.org syt_x"),
+            Err(ChannelError::MatrixArguments)
+        );
+    }
+
+    #[test]
+    fn test_parse_self_hosted() {
+        assert_eq!(
+            Channel::parse("ntfy http://ntfy.example.org/mandown"),
+            Ok(Channel::Ntfy {
+                url: "http://ntfy.example.org/mandown".to_string(),
+                token: None
+            })
+        );
+        assert_eq!(
+            Channel::parse("gotify https://gotify.example.org AbC.123"),
+            Ok(Channel::Gotify {
+                url: "https://gotify.example.org".to_string(),
+                token: "AbC.123".to_string()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refuses_internal_servers() {
+        for spec in [
+            "ntfy http://127.0.0.1/mandown",
+            "ntfy http://localhost:8080/mandown",
+            "ntfy http://[::1]/mandown",
+            "gotify http://169.254.169.254 token",
+            "gotify http://10.0.0.5 token",
+            "matrix http://127.0.0.1:8008 !ops:example.org syt_x",
+            "matrix http://169.254.169.254 !ops:example.org syt_x",
+        ] {
+            let channel = Channel::parse(spec).unwrap();
+            assert!(!channel.is_public().await, "{spec}");
+        }
+        let channel = Channel::parse("ntfy https://1.1.1.1/mandown").unwrap();
+        assert!(channel.is_public().await);
+    }
+
+    #[test]
+    fn test_severity() {
+        let website = |previous, status, failure| Website {
+            previous_status: Some(previous),
+            status,
+            failure,
+            ..Default::default()
+        };
+        assert_eq!(severity(&website(200, 0, Some(FailureKind::Timeout))), 5);
+        assert_eq!(severity(&website(200, 503, None)), 4);
+        assert_eq!(severity(&website(200, 0, Some(FailureKind::InvalidUrl))), 3);
+        assert_eq!(severity(&website(500, 503, None)), 3);
+        assert_eq!(severity(&website(503, 200, None)), 2);
+    }
+
+    #[test]
+    fn test_describe_hides_path() {
+        let channel =
+            Channel::parse("slack https://hooks.slack.com/services/T0/B0/secret").unwrap();
+        assert_eq!(channel.describe(), "Slack (hooks.slack.com)");
+    }
+
+    #[test]
+    fn test_headline() {
+        let website = Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(503),
+            status: 200,
+            ..Default::default()
+        };
+        assert_eq!(
+            headline(&website, Language::En),
+            "https://example.com is back up"
+        );
+
+        // Plain text, with the URL as it is
+        let website = Website {
+            url: "https://example.com/?a=1&b=2".to_string(),
+            previous_status: Some(200),
+            status: 0,
+            failure: Some(FailureKind::Timeout),
+            ..Default::default()
+        };
+        assert_eq!(
+            headline(&website, Language::De),
+            "https://example.com/?a=1&b=2 ist nicht erreichbar"
+        );
+        assert_eq!(
+            status_line(&website, Language::De, "*"),
+            "Status: *0* (vorher 200), Zeitüberschreitung"
+        );
+    }
+}
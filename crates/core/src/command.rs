@@ -1,10 +1,21 @@
-use crate::handler::{handle_about, handle_clear, handle_list, handle_track, handle_untrack};
+use crate::handler::{
+    CheckClient, chat_language, handle_about, handle_ack, handle_callback, handle_check,
+    handle_clear, handle_connect, handle_email, handle_escalate, handle_language, handle_list,
+    handle_maintenance, handle_pause, handle_resume, handle_settings, handle_tag, handle_template,
+    handle_track, handle_untrack, handle_webhook,
+};
+use crate::html::send_html;
+use crate::i18n::{Language, lookup, t};
+use crate::mongo::reactivate_chat;
 use mongodb::{Collection, bson::Document};
 use std::fmt::Debug;
 use std::sync::Arc;
+use teloxide::RequestError;
+use teloxide::dispatching::DefaultKey;
 use teloxide::prelude::*;
 use teloxide::update_listeners::UpdateListener;
 use teloxide::utils::command::BotCommands;
+use teloxide::utils::html::escape;
 
 #[derive(BotCommands, Clone)]
 #[command(
@@ -14,18 +25,44 @@ use teloxide::utils::command::BotCommands;
 enum Command {
     #[command(description = "About ManDown")]
     About,
-    #[command(description = "Clear your list of your followed domains")]
+    #[command(description = "Acknowledge open incidents, for one site or all of them")]
+    Ack(String),
+    #[command(description = "Check a website once without tracking it")]
+    Check(String),
+    #[command(description = "Clear this chat's list of followed domains")]
     Clear(String),
+    #[command(description = "Also post status changes to Slack, Discord, Matrix, ntfy or Gotify")]
+    Connect(String),
+    #[command(description = "Also receive alerts by email at a verified address")]
+    Email(String),
+    #[command(description = "Forward unacknowledged incidents to another chat")]
+    Escalate(String),
     #[command(description = "I am here to help!")]
     Help,
-    #[command(description = "Get a list of your followed domains")]
-    List,
+    #[command(description = "Choose the language of replies and alerts")]
+    Language(String),
+    #[command(description = "List tracked sites, filtered by down, up, paused or tag:<name>")]
+    List(String),
+    #[command(description = "Manage weekly maintenance windows that withhold alerts")]
+    Maintenance(String),
+    #[command(description = "Pause checks for a site, optionally for a while (e.g. 2h)")]
+    Pause(String),
+    #[command(description = "Resume checks for a paused site")]
+    Resume(String),
+    #[command(description = "Quiet hours and which status changes to notify about")]
+    Settings(String),
     #[command(description = "I am here to help!")]
     Start,
+    #[command(description = "Tag sites to filter, pause and notify about them together")]
+    Tag(String),
+    #[command(description = "Change the wording of alerts")]
+    Template(String),
     #[command(description = "Add to the list of tracked websites")]
     Track(String),
     #[command(description = "Remove from the list of tracked websites")]
     Untrack(String),
+    #[command(description = "Post status changes as signed JSON to your own URLs")]
+    Webhook(String),
 }
 
 pub async fn start_command(
@@ -33,13 +70,8 @@ pub async fn start_command(
     collection: Arc<Collection<Document>>,
     client: Arc<reqwest::Client>,
 ) {
-    // Start the bot's command loop (long polling)
-    Command::repl(bot, move |bot, msg, cmd| {
-        let collection = collection.clone();
-        let client = client.clone();
-        async move { answer(bot, msg, cmd, collection, client).await }
-    })
-    .await;
+    // Start the bot's dispatcher (long polling)
+    build_dispatcher(bot, collection, client).dispatch().await;
 }
 
 pub async fn start_command_with_listener<L>(
@@ -51,16 +83,66 @@ pub async fn start_command_with_listener<L>(
     L: UpdateListener + Send,
     L::Err: Debug + Send,
 {
-    Command::repl_with_listener(
-        bot,
-        move |bot, msg, cmd| {
-            let collection = collection.clone();
-            let client = client.clone();
-            async move { answer(bot, msg, cmd, collection, client).await }
-        },
-        listener,
-    )
-    .await;
+    build_dispatcher(bot, collection, client)
+        .dispatch_with_listener(
+            listener,
+            LoggingErrorHandler::with_custom_text("An error from the update listener"),
+        )
+        .await;
+}
+
+// Commands arrive as messages in private chats and groups, and as channel
+// posts in channels; both are answered the same way. Button presses on
+// alerts arrive as callback queries. Any message at all shows that a chat
+// the poller gave up on is reachable again.
+fn build_dispatcher(
+    bot: Bot,
+    collection: Arc<Collection<Document>>,
+    client: Arc<reqwest::Client>,
+) -> Dispatcher<Bot, RequestError, DefaultKey> {
+    let command = dptree::entry().filter_command::<Command>().endpoint(answer);
+    let handler = dptree::entry()
+        .branch(
+            Update::filter_message()
+                .inspect_async(reactivate)
+                .chain(command.clone()),
+        )
+        .branch(
+            Update::filter_channel_post()
+                .inspect_async(reactivate)
+                .chain(command),
+        )
+        .branch(Update::filter_callback_query().endpoint(answer_callback));
+    let check_client = Arc::new(CheckClient::default());
+
+    Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![collection, client, check_client])
+        .default_handler(|_| async {})
+        .enable_ctrlc_handler()
+        .build()
+}
+
+async fn reactivate(msg: Message, collection: Arc<Collection<Document>>) {
+    match reactivate_chat(&collection, msg.chat.id.0).await {
+        Ok(true) => log::info!("Chat {} is back, checking its sites again", msg.chat.id),
+        Ok(false) => {}
+        Err(e) => log::error!("Error reactivating chat {}: {e}", msg.chat.id),
+    }
+}
+
+/// The command list in the chat's language. Commands without a translation
+/// keep their English description.
+fn help(language: Language) -> String {
+    let mut text = t!(language, "help.title");
+    text.push('\n');
+    for command in Command::bot_commands() {
+        let name = command.command.trim_start_matches('/');
+        // Translations are HTML already, the English descriptions are not
+        let description = lookup(language, &format!("command.{name}"))
+            .map_or_else(|| escape(&command.description), str::to_string);
+        text.push_str(&format!("\n/{name} — {description}"));
+    }
+    text
 }
 
 async fn answer(
@@ -69,25 +151,66 @@ async fn answer(
     cmd: Command,
     collection: Arc<Collection<Document>>,
     client: Arc<reqwest::Client>,
+    check_client: Arc<CheckClient>,
 ) -> ResponseResult<()> {
+    let language = chat_language(&collection, &msg).await;
     match cmd {
-        Command::About => handle_about(bot, msg).await?,
-        Command::Clear(confirmation) => handle_clear(bot, msg, &collection, confirmation).await?,
-        Command::Help => {
-            bot.send_message(msg.chat.id, Command::descriptions().to_string())
-                .await?;
+        Command::About => handle_about(bot, msg, language).await?,
+        Command::Ack(website) => {
+            handle_ack(bot, msg, language, website.to_lowercase(), &collection).await?
+        }
+        Command::Check(website) => {
+            handle_check(bot, msg, language, website.to_lowercase(), &check_client).await?
         }
-        Command::List => handle_list(bot, msg, &collection).await?,
-        Command::Start => {
-            bot.send_message(msg.chat.id, Command::descriptions().to_string())
-                .await?;
+        Command::Clear(confirmation) => {
+            handle_clear(bot, msg, language, &collection, confirmation).await?
         }
+        Command::Connect(args) => handle_connect(bot, msg, language, args, &collection).await?,
+        Command::Email(args) => handle_email(bot, msg, language, args, &collection).await?,
+        Command::Escalate(args) => {
+            handle_escalate(bot, msg, language, args.to_lowercase(), &collection).await?
+        }
+        Command::Help | Command::Start => {
+            send_html(&bot, msg.chat.id, help(language)).await?;
+        }
+        Command::Language(code) => handle_language(bot, msg, language, code, &collection).await?,
+        Command::List(args) => handle_list(bot, msg, language, args, &collection).await?,
+        Command::Maintenance(args) => {
+            handle_maintenance(bot, msg, language, args, &collection).await?
+        }
+        Command::Pause(args) => {
+            handle_pause(bot, msg, language, args.to_lowercase(), &collection).await?
+        }
+        Command::Resume(website) => {
+            handle_resume(bot, msg, language, website.to_lowercase(), &collection).await?
+        }
+        Command::Settings(args) => handle_settings(bot, msg, language, args, &collection).await?,
+        Command::Tag(args) => handle_tag(bot, msg, language, args, &collection).await?,
+        Command::Template(args) => handle_template(bot, msg, language, args, &collection).await?,
         Command::Track(website) => {
-            handle_track(bot, msg, website.to_lowercase(), &collection, client).await?
+            handle_track(
+                bot,
+                msg,
+                language,
+                website.to_lowercase(),
+                &collection,
+                client,
+            )
+            .await?
         }
         Command::Untrack(website) => {
-            handle_untrack(bot, msg, website.to_lowercase(), &collection).await?
+            handle_untrack(bot, msg, language, website.to_lowercase(), &collection).await?
         }
+        Command::Webhook(args) => handle_webhook(bot, msg, language, args, &collection).await?,
     };
     Ok(())
 }
+
+async fn answer_callback(
+    bot: Bot,
+    query: CallbackQuery,
+    collection: Arc<Collection<Document>>,
+    client: Arc<reqwest::Client>,
+) -> ResponseResult<()> {
+    handle_callback(bot, query, &collection, client).await
+}
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::mongo::Website;
+
+/// How often a chat in digest mode receives its collected changes.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestInterval {
+    Hourly,
+    Daily,
+}
+
+impl DigestInterval {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.to_lowercase().as_str() {
+            "hourly" => Some(DigestInterval::Hourly),
+            "daily" => Some(DigestInterval::Daily),
+            _ => None,
+        }
+    }
+
+    pub fn duration(self) -> Duration {
+        match self {
+            DigestInterval::Hourly => Duration::hours(1),
+            DigestInterval::Daily => Duration::days(1),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DigestInterval::Hourly => "hourly",
+            DigestInterval::Daily => "daily",
+        }
+    }
+}
+
+/// A status change as reported in a grouped alert or a digest.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Change {
+    pub url: String,
+    pub from: Option<i32>,
+    pub to: i32,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub at: DateTime<Utc>,
+}
+
+impl From<&Website> for Change {
+    fn from(website: &Website) -> Self {
+        Change {
+            url: website.url.clone(),
+            from: website.previous_status,
+            to: website.status,
+            at: website.last_updated,
+        }
+    }
+}
+
+/// Whether the queued changes of a chat should be sent now. The first digest
+/// goes out one interval after the oldest queued change; a chat that left
+/// digest mode gets whatever is left straight away.
+pub fn digest_due(
+    interval: Option<DigestInterval>,
+    last_sent: Option<DateTime<Utc>>,
+    queue: &[Change],
+    now: DateTime<Utc>,
+) -> bool {
+    let Some(oldest) = queue.iter().map(|change| change.at).min() else {
+        return false;
+    };
+    let Some(interval) = interval else {
+        return true;
+    };
+    now - last_sent.unwrap_or(oldest) >= interval.duration()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(at: DateTime<Utc>) -> Change {
+        Change {
+            url: "https://example.com".to_string(),
+            from: Some(200),
+            to: 503,
+            at,
+        }
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(
+            DigestInterval::parse("Hourly"),
+            Some(DigestInterval::Hourly)
+        );
+        assert_eq!(DigestInterval::parse("daily"), Some(DigestInterval::Daily));
+        assert_eq!(DigestInterval::parse("weekly"), None);
+    }
+
+    #[test]
+    fn test_nothing_queued() {
+        let now = Utc::now();
+        assert!(!digest_due(Some(DigestInterval::Hourly), None, &[], now));
+        assert!(!digest_due(None, None, &[], now));
+    }
+
+    #[test]
+    fn test_first_digest_waits_for_oldest_change() {
+        let now = Utc::now();
+        let queue = [change(now - Duration::minutes(70)), change(now)];
+        assert!(digest_due(Some(DigestInterval::Hourly), None, &queue, now));
+        assert!(!digest_due(Some(DigestInterval::Daily), None, &queue, now));
+    }
+
+    #[test]
+    fn test_counts_from_last_digest() {
+        let now = Utc::now();
+        let queue = [change(now - Duration::hours(5))];
+        let last_sent = Some(now - Duration::minutes(30));
+        assert!(!digest_due(
+            Some(DigestInterval::Hourly),
+            last_sent,
+            &queue,
+            now
+        ));
+        assert!(digest_due(
+            Some(DigestInterval::Hourly),
+            Some(now - Duration::hours(1)),
+            &queue,
+            now
+        ));
+    }
+
+    #[test]
+    fn test_flushes_after_leaving_digest_mode() {
+        let now = Utc::now();
+        assert!(digest_due(None, Some(now), &[change(now)], now));
+    }
+}
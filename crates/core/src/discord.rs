@@ -0,0 +1,63 @@
+use serde_json::{Value, json};
+
+use crate::channel::{colour, headline};
+use crate::i18n::{Language, tr};
+use crate::mongo::Website;
+
+/// A single embed linking to the site, coloured by the kind of change.
+pub fn payload(website: &Website, language: Language) -> Value {
+    let mut fields = vec![json!({
+        "name": tr(language, "list.status"),
+        "value": website.status.to_string(),
+        "inline": true,
+    })];
+    if let Some(previous) = website.previous_status {
+        fields.push(json!({
+            "name": tr(language, "channel.was"),
+            "value": previous.to_string(),
+            "inline": true,
+        }));
+    }
+    if let Some(failure) = website.failure {
+        fields.push(json!({
+            "name": tr(language, "channel.failure"),
+            "value": failure.describe(language),
+            "inline": true,
+        }));
+    }
+
+    json!({
+        "username": "ManDown",
+        "embeds": [{
+            "title": headline(website, language),
+            "url": website.url,
+            "color": colour(website.transition()),
+            "fields": fields,
+            "timestamp": website.last_updated.to_rfc3339(),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let website = Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(500),
+            status: 503,
+            ..Default::default()
+        };
+        let embed = &payload(&website, Language::En)["embeds"][0];
+
+        assert_eq!(embed["title"], "https://example.com changed status");
+        assert_eq!(embed["url"], "https://example.com");
+        assert_eq!(embed["color"], 0xf79009);
+        assert_eq!(embed["fields"][0]["value"], "503");
+        assert_eq!(embed["fields"][1]["name"], "Was");
+        assert_eq!(embed["fields"][1]["value"], "500");
+        assert_eq!(embed["fields"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::Duration;
+
+/// Parse durations such as `90s`, `30m`, `2h`, `1d` or `1h30m`.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let amount: i64 = digits.parse().ok()?;
+        digits.clear();
+        let part = match c.to_ascii_lowercase() {
+            's' => Duration::try_seconds(amount)?,
+            'm' => Duration::try_minutes(amount)?,
+            'h' => Duration::try_hours(amount)?,
+            'd' => Duration::try_days(amount)?,
+            _ => return None,
+        };
+        total = total.checked_add(&part)?;
+    }
+
+    // Trailing digits without a unit are ambiguous
+    if !digits.is_empty() || total <= Duration::zero() {
+        return None;
+    }
+    Some(total)
+}
+
+/// Render a duration with its two most significant units, e.g. `2h 5m`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{seconds}s"),
+        (0, 0, m) => format!("{m}m"),
+        (0, h, 0) => format!("{h}h"),
+        (0, h, m) => format!("{h}h {m}m"),
+        (d, 0, _) => format!("{d}d"),
+        (d, h, _) => format!("{d}d {h}h"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_units() {
+        assert_eq!(parse_duration("90s"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("30m"), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration("2H"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("1d"), Some(Duration::days(1)));
+    }
+
+    #[test]
+    fn test_parse_combined() {
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration(" 1d12h "), Some(Duration::hours(36)));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("5w"), None);
+        assert_eq!(parse_duration("-5m"), None);
+        assert_eq!(parse_duration("99999999999999999999d"), None);
+    }
+
+    #[test]
+    fn test_parse_overflowing_sum() {
+        assert_eq!(parse_duration("9223372036854d9223372036854d"), None);
+        assert_eq!(parse_duration("106751991167d106751991167d"), None);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_duration(Duration::seconds(42)), "42s");
+        assert_eq!(format_duration(Duration::minutes(5)), "5m");
+        assert_eq!(format_duration(Duration::minutes(125)), "2h 5m");
+        assert_eq!(format_duration(Duration::hours(3)), "3h");
+        assert_eq!(format_duration(Duration::hours(50)), "2d 2h");
+        assert_eq!(format_duration(Duration::days(3)), "3d");
+    }
+}
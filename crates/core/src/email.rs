@@ -0,0 +1,506 @@
+use chrono::{DateTime, Duration, Utc};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::extension::ClientId;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::alert::render_alert;
+use crate::html::strip_tags;
+use crate::i18n::t;
+use crate::mongo::{ChatSettings, Website};
+use crate::notify::{Event, Notifier};
+
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How long a verification code can be used.
+pub const CODE_TTL_MINUTES: i64 = 15;
+/// Wrong guesses after which a code is thrown away.
+pub const MAX_ATTEMPTS: u32 = 5;
+/// Verification emails a chat can have sent per hour.
+pub const MAX_CODES_PER_HOUR: u32 = 3;
+
+/// Where alerts are emailed, stored per chat. Alerts only go out once the
+/// address has been confirmed with the code sent to it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EmailSubscription {
+    pub address: String,
+    pub verified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub code_expires: Option<DateTime<Utc>>,
+    /// Wrong codes entered for the current one
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// The outcome of `/email verify <code>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Verified,
+    WrongCode,
+    /// The code ran out or was guessed at too often; a new one is needed
+    Expired,
+}
+
+impl EmailSubscription {
+    pub fn pending(address: &str, now: DateTime<Utc>) -> Self {
+        EmailSubscription {
+            address: address.to_string(),
+            verified: false,
+            code: Some(format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))),
+            code_expires: Some(now + Duration::minutes(CODE_TTL_MINUTES)),
+            attempts: 0,
+        }
+    }
+
+    /// Check a code against the pending one. Each wrong guess counts, and
+    /// the code is dropped once it runs out or after `MAX_ATTEMPTS` guesses.
+    pub fn verify(&mut self, code: &str, now: DateTime<Utc>) -> Verification {
+        let live = self.code_expires.is_some_and(|expires| now < expires);
+        let Some(expected) = self.code.as_deref().filter(|_| live) else {
+            self.code = None;
+            return Verification::Expired;
+        };
+        if code == expected {
+            self.verified = true;
+            self.code = None;
+            self.code_expires = None;
+            self.attempts = 0;
+            return Verification::Verified;
+        }
+        self.attempts += 1;
+        if self.attempts >= MAX_ATTEMPTS {
+            self.code = None;
+        }
+        Verification::WrongCode
+    }
+}
+
+/// Verification emails a chat sent in the current hour. Kept apart from the
+/// subscription, so turning email off does not start the count over.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CodeQuota {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sent: u32,
+}
+
+impl CodeQuota {
+    /// Count one more email, or return false once the hour's are used up.
+    pub fn take(&mut self, now: DateTime<Utc>) -> bool {
+        if self
+            .since
+            .is_none_or(|since| now - since >= Duration::hours(1))
+        {
+            *self = CodeQuota {
+                since: Some(now),
+                sent: 0,
+            };
+        }
+        if self.sent >= MAX_CODES_PER_HOUR {
+            return false;
+        }
+        self.sent += 1;
+        true
+    }
+}
+
+/// A loose sanity check; the verification email is the real test.
+pub fn is_valid_address(address: &str) -> bool {
+    let Some((local, domain)) = address.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains('@')
+        && !address.contains(|c: char| c.is_whitespace() || "<>&,;\"".contains(c))
+}
+
+/// How the connection to the SMTP server is encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// Upgrade a plain connection, usually on port 587
+    Starttls,
+    /// TLS from the start, usually on port 465
+    Implicit,
+    /// No encryption, for a local SMTP sink
+    Off,
+}
+
+/// SMTP server settings, read from `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`,
+/// `SMTP_PASSWORD`, `SMTP_FROM` and `SMTP_TLS` (`starttls`, `tls` or `off`).
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+    pub from: String,
+    pub tls: SmtpTls,
+}
+
+impl SmtpConfig {
+    /// `None` unless `SMTP_HOST` is set, which leaves email turned off. The
+    /// binaries check the settings at startup, so the error only shows up
+    /// here if the environment changed since.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(host) = dotenvy::var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let tls = match dotenvy::var("SMTP_TLS").as_deref() {
+            Err(_) | Ok("starttls") => SmtpTls::Starttls,
+            Ok("tls") => SmtpTls::Implicit,
+            Ok("off") => SmtpTls::Off,
+            Ok(other) => {
+                return Err(format!(
+                    "SMTP_TLS must be starttls, tls or off, not {other}"
+                ));
+            }
+        };
+        let default_port = match tls {
+            SmtpTls::Implicit => "465",
+            SmtpTls::Starttls | SmtpTls::Off => "587",
+        };
+        let port = dotenvy::var("SMTP_PORT")
+            .unwrap_or(default_port.to_string())
+            .parse()
+            .map_err(|_| "SMTP_PORT must be a number".to_string())?;
+        let credentials = match (dotenvy::var("SMTP_USERNAME"), dotenvy::var("SMTP_PASSWORD")) {
+            (Ok(username), Ok(password)) => Some((username, password)),
+            _ => None,
+        };
+        let from = dotenvy::var("SMTP_FROM")
+            .map_err(|_| "SMTP_FROM must be set when SMTP_HOST is".to_string())?;
+
+        Ok(Some(SmtpConfig {
+            host,
+            port,
+            credentials,
+            from,
+            tls,
+        }))
+    }
+
+    fn transport(
+        &self,
+    ) -> Result<AsyncSmtpTransport<Tokio1Executor>, Box<dyn Error + Send + Sync>> {
+        let builder = match self.tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?,
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?,
+            SmtpTls::Off => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+        };
+        let domain = self.from.rsplit('@').next().unwrap_or("localhost");
+        let mut builder = builder
+            .port(self.port)
+            .timeout(Some(TIMEOUT))
+            .hello_name(ClientId::Domain(domain.to_string()));
+        if let Some((username, password)) = &self.credentials {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(builder.build())
+    }
+}
+
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+impl Email {
+    /// Build an email from an HTML message as sent to Telegram.
+    pub fn from_html(to: &str, subject: &str, html: &str) -> Self {
+        Email {
+            to: to.to_string(),
+            subject: subject.to_string(),
+            text: strip_tags(html),
+            html: format!(
+                "<p>{}</p>",
+                html.replace("\n\n", "</p><p>").replace('\n', "<br>")
+            ),
+        }
+    }
+
+    fn to_message(&self, from: &str) -> Result<Message, Box<dyn Error + Send + Sync>> {
+        let message = Message::builder()
+            .from(from.parse::<Mailbox>()?)
+            .to(self.to.parse::<Mailbox>()?)
+            .subject(&self.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                self.text.clone(),
+                self.html.clone(),
+            ))?;
+        Ok(message)
+    }
+}
+
+/// Deliver an email through the configured SMTP server.
+pub async fn send_email(
+    config: &SmtpConfig,
+    email: &Email,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let message = email.to_message(&config.from)?;
+    config.transport()?.send(message).await?;
+    Ok(())
+}
+
+/// Emails status changes to chats with a verified address, one message per
+/// chat and sweep.
+pub struct EmailNotifier {
+    config: SmtpConfig,
+}
+
+impl EmailNotifier {
+    pub fn new(config: SmtpConfig) -> Self {
+        EmailNotifier { config }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+        let Some(subscription) = chat.email.as_ref().filter(|email| email.verified) else {
+            return true;
+        };
+        let websites: Vec<&Website> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Changed(website) => Some(website),
+                _ => None,
+            })
+            .collect();
+
+        let language = chat.language.unwrap_or_default();
+        // Subjects are plain text, while catalogue messages are HTML
+        let subject = match websites[..] {
+            [] => return true,
+            [website] => t!(
+                language,
+                "email.alert_subject",
+                site = website.url,
+                status = website.status
+            ),
+            _ => t!(language, "alert.grouped", count = websites.len()),
+        };
+        let subject = strip_tags(&subject);
+        let html = websites
+            .iter()
+            .map(|website| render_alert(chat, website))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let email = Email::from_html(&subscription.address, &subject, &html);
+        match send_email(&self.config, &email).await {
+            Ok(()) => true,
+            Err(e) => {
+                log::info!("Failed to email {}: {e}", subscription.address);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    // A local SMTP sink that accepts one message and records the session
+    async fn smtp_sink() -> (u16, Arc<Mutex<String>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let session = Arc::new(Mutex::new(String::new()));
+        let log = session.clone();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            socket.get_mut().write_all(b"220 sink\r\n").await.unwrap();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if socket.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                log.lock().unwrap().push_str(&line);
+                let reply: &[u8] = match line.trim_end() {
+                    "." if in_data => {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    }
+                    _ if in_data => continue,
+                    l if l.starts_with("EHLO") => b"250-sink\r\n250 AUTH PLAIN\r\n",
+                    l if l.starts_with("AUTH") => b"235 ok\r\n",
+                    "DATA" => {
+                        in_data = true;
+                        b"354 go ahead\r\n"
+                    }
+                    "QUIT" => b"221 bye\r\n",
+                    _ => b"250 ok\r\n",
+                };
+                socket.get_mut().write_all(reply).await.unwrap();
+            }
+        });
+        (port, session)
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            credentials: Some(("bot".to_string(), "secret".to_string())),
+            from: "alerts@example.com".to_string(),
+            tls: SmtpTls::Off,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_to_sink() {
+        let (port, session) = smtp_sink().await;
+        let email = Email::from_html("ops@example.com", "example.com: status 503", "Site: x");
+        send_email(&config(port), &email).await.unwrap();
+
+        let session = session.lock().unwrap();
+        assert!(session.contains("EHLO example.com\r\n"));
+        // Base64 of "\0bot\0secret"
+        assert!(session.contains("AUTH PLAIN AGJvdABzZWNyZXQ=\r\n"));
+        assert!(session.contains("MAIL FROM:<alerts@example.com>\r\n"));
+        assert!(session.contains("RCPT TO:<ops@example.com>\r\n"));
+        assert!(session.contains("Subject: example.com: status 503\r\n"));
+        assert!(session.ends_with("QUIT\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_recipient() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            socket.get_mut().write_all(b"220 sink\r\n").await.unwrap();
+            let mut line = String::new();
+            while socket.read_line(&mut line).await.unwrap_or(0) > 0 {
+                let reply: &[u8] = if line.starts_with("RCPT") {
+                    b"550 no such user\r\n"
+                } else {
+                    b"250 ok\r\n"
+                };
+                socket.get_mut().write_all(reply).await.unwrap();
+                line.clear();
+            }
+        });
+
+        let mut config = config(port);
+        config.credentials = None;
+        let email = Email::from_html("nobody@example.com", "test", "test");
+        let error = send_email(&config, &email).await.unwrap_err();
+        assert!(error.to_string().contains("no such user"), "{error}");
+    }
+
+    #[test]
+    fn test_message_parts() {
+        let email = Email::from_html(
+            "ops@example.com",
+            "Schade ✗",
+            "Site: x\n\nStatus: <a href='https://httpstatuses.com/503'>503</a>",
+        );
+        assert_eq!(email.text, "Site: x\n\nStatus: 503");
+        assert!(email.html.contains("<p>Site: x</p><p>Status: <a"));
+
+        let message = email.to_message("alerts@example.com").unwrap();
+        let message = String::from_utf8(message.formatted()).unwrap();
+        assert!(message.contains("Content-Type: multipart/alternative"));
+        assert!(message.contains("Content-Type: text/plain"));
+        assert!(message.contains("Content-Type: text/html"));
+        assert!(!message.contains("Schade ✗"), "{message}");
+        assert!(message.lines().all(|line| line.len() <= 998));
+
+        assert!(email.to_message("not an address").is_err());
+    }
+
+    #[test]
+    fn test_valid_address() {
+        assert!(is_valid_address("ops@example.com"));
+        assert!(is_valid_address("first.last+alerts@mail.example.org"));
+        assert!(!is_valid_address("example.com"));
+        assert!(!is_valid_address("@example.com"));
+        assert!(!is_valid_address("ops@localhost"));
+        assert!(!is_valid_address("ops@@example.com"));
+        assert!(!is_valid_address("ops@example.com>"));
+        assert!(!is_valid_address("o ps@example.com"));
+    }
+
+    #[test]
+    fn test_verification_code() {
+        let subscription = EmailSubscription::pending("ops@example.com", Utc::now());
+        let code = subscription.code.unwrap();
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+        assert!(!subscription.verified);
+    }
+
+    #[test]
+    fn test_verify() {
+        let now = Utc::now();
+        let mut subscription = EmailSubscription::pending("ops@example.com", now);
+        let code = subscription.code.clone().unwrap();
+
+        assert_eq!(subscription.verify("nope", now), Verification::WrongCode);
+        assert_eq!(subscription.verify(&code, now), Verification::Verified);
+        assert!(subscription.verified);
+        assert_eq!(subscription.code, None);
+    }
+
+    #[test]
+    fn test_code_expires() {
+        let now = Utc::now();
+        let mut subscription = EmailSubscription::pending("ops@example.com", now);
+        let code = subscription.code.clone().unwrap();
+
+        let later = now + Duration::minutes(CODE_TTL_MINUTES);
+        assert_eq!(subscription.verify(&code, later), Verification::Expired);
+        assert!(!subscription.verified);
+    }
+
+    #[test]
+    fn test_code_dropped_after_attempts() {
+        let now = Utc::now();
+        let mut subscription = EmailSubscription::pending("ops@example.com", now);
+        let code = subscription.code.clone().unwrap();
+
+        for _ in 0..MAX_ATTEMPTS {
+            assert_eq!(subscription.verify("wrong", now), Verification::WrongCode);
+        }
+        assert_eq!(subscription.verify(&code, now), Verification::Expired);
+        assert!(!subscription.verified);
+    }
+
+    #[test]
+    fn test_codes_per_hour() {
+        let now = Utc::now();
+        let mut quota = CodeQuota {
+            since: Some(now - Duration::hours(2)),
+            sent: MAX_CODES_PER_HOUR,
+        };
+        for _ in 0..MAX_CODES_PER_HOUR {
+            assert!(quota.take(now));
+        }
+        assert!(!quota.take(now + Duration::minutes(59)));
+        assert!(quota.take(now + Duration::hours(1)));
+    }
+}
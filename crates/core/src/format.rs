@@ -1,19 +1,281 @@
+use crate::digest::Change;
+use crate::duration::format_duration;
+use crate::http::Probe;
+use crate::i18n::{Language, t};
+use crate::list::{Health, Page};
 use crate::mongo::Website;
+use crate::webhook::Delivery;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use teloxide::utils::html::escape;
 
-pub fn format_website_list(websites: &[Website]) -> String {
-    if websites.is_empty() {
-        return "You aren't tracking any sites yet.".to_string();
+// Columns a cell takes up in a monospaced font; emoji are twice as wide
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c as u32 {
+            0xfe0f => 0,
+            0x2300..=0x23ff | 0x2600..=0x27bf | 0x1f000.. => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// A table whose columns are as wide as their widest cell, for a `<pre>`
+/// block. The first row is the header.
+fn table(rows: &[Vec<String>]) -> String {
+    let columns = rows.first().map_or(0, Vec::len);
+    let widths: Vec<usize> = (0..columns)
+        .map(|n| {
+            rows.iter()
+                .map(|row| display_width(&row[n]))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |row: &Vec<String>| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                let padding = width - display_width(cell);
+                format!("{cell}{}", " ".repeat(padding))
+            })
+            .collect();
+        escape(cells.join(" | ").trim_end())
+    };
+
+    let mut table = String::new();
+    for (n, row) in rows.iter().enumerate() {
+        table.push_str(&line(row));
+        table.push('\n');
+        if n == 0 {
+            let width = widths.iter().sum::<usize>() + 3 * columns.saturating_sub(1);
+            table.push_str(&"-".repeat(width));
+            table.push('\n');
+        }
     }
+    table
+}
 
-    let mut table = "Here are your tracked domains:\n\n<pre>".to_string();
-    table.push_str(&format!("{:<30} | {:<6}\n", "URL", "Status"));
-    table.push_str(&"-".repeat(40));
-    table.push('\n');
+pub fn format_website_list(language: Language, page: &Page) -> String {
+    if page.tracked == 0 {
+        return t!(language, "list.empty");
+    }
+    if page.sites.is_empty() {
+        return t!(language, "list.no_match");
+    }
 
-    for site in websites {
-        table.push_str(&format!("{:<30} | {:<6}\n", site.url, site.status));
+    let now = Utc::now();
+    let mut rows = vec![vec![
+        String::new(),
+        "URL".to_string(),
+        t!(language, "list.status"),
+        t!(language, "list.state"),
+        t!(language, "list.checked"),
+        t!(language, "list.response"),
+    ]];
+    for site in &page.sites {
+        let health = Health::of(site);
+        let indicator = if site.is_paused(now) {
+            "⏸"
+        } else {
+            health.indicator()
+        };
+        let since = format_duration(now - site.last_updated);
+        let state = match health {
+            Health::Down => t!(language, "list.down_for", duration = since),
+            Health::Up | Health::Degraded => t!(language, "list.up_for", duration = since),
+        };
+        let checked = site.last_checked.map_or("-".to_string(), |at| {
+            t!(language, "list.ago", duration = format_duration(now - at))
+        });
+        let response = site
+            .latency_ms
+            .map_or("-".to_string(), |ms| format!("{ms} ms"));
+        rows.push(vec![
+            indicator.to_string(),
+            site.url.clone(),
+            site.status.to_string(),
+            state,
+            checked,
+            response,
+        ]);
     }
-    table.push_str("</pre>");
 
-    table
+    let mut list = format!(
+        "{}\n\n<pre>{}</pre>",
+        t!(language, "list.title"),
+        table(&rows)
+    );
+    if page.pages > 1 {
+        let footer = t!(
+            language,
+            "list.page",
+            page = page.number + 1,
+            pages = page.pages
+        );
+        list.push_str(&format!("\n{footer}"));
+    }
+    list
+}
+
+/// The tags in use in a chat, with how many sites carry each.
+pub fn format_tags(language: Language, websites: &[Website]) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in websites.iter().flat_map(|website| &website.tags) {
+        *counts.entry(tag).or_default() += 1;
+    }
+    if counts.is_empty() {
+        return t!(language, "tag.none");
+    }
+
+    let mut message = format!("<b>{}</b>", t!(language, "tag.title"));
+    for (tag, count) in counts {
+        message.push('\n');
+        message.push_str(&t!(language, "tag.count", tag = tag, count = count));
+    }
+    message
+}
+
+/// A table of status changes, used for grouped alerts and digests.
+pub fn format_changes(language: Language, title: &str, changes: &[Change]) -> String {
+    let mut rows = vec![vec![
+        "URL".to_string(),
+        t!(language, "changes.was"),
+        t!(language, "changes.now"),
+    ]];
+    for change in changes {
+        let from = change
+            .from
+            .map_or("-".to_string(), |status| status.to_string());
+        rows.push(vec![change.url.clone(), from, change.to.to_string()]);
+    }
+
+    format!("{title}\n\n<pre>{}</pre>", table(&rows))
+}
+
+pub fn format_deliveries(language: Language, deliveries: &[Delivery]) -> String {
+    if deliveries.is_empty() {
+        return t!(language, "deliveries.none");
+    }
+
+    let mut log = format!("{}\n", t!(language, "deliveries.title"));
+    for delivery in deliveries {
+        let outcome = match (&delivery.error, delivery.status) {
+            (None, Some(status)) => format!("✅ {status}"),
+            (None, None) => "✅".to_string(),
+            (Some(error), _) => format!("❌ {}", escape(error)),
+        };
+        log.push_str(&format!(
+            "{} {} → {}: {outcome} ({})\n",
+            delivery.at.format("%Y-%m-%d %H:%M"),
+            escape(&delivery.site),
+            escape(&delivery.url),
+            t!(
+                language,
+                "deliveries.attempts",
+                attempts = delivery.attempts
+            )
+        ));
+    }
+    log
+}
+
+pub fn format_probe_report(language: Language, url: &str, probe: &Probe) -> String {
+    let mut report = format!("<b>{}</b>\n", escape(url));
+
+    let status = match probe.failure {
+        Some(failure) => t!(
+            language,
+            "probe.failed",
+            failure = failure.describe(language)
+        ),
+        None => t!(language, "probe.status", status = probe.status),
+    };
+    report.push_str(&format!("{status}\n"));
+    let latency = t!(language, "probe.latency", ms = probe.latency.as_millis());
+    report.push_str(&format!("{latency}\n"));
+
+    if !probe.redirects.is_empty() {
+        let redirects = t!(
+            language,
+            "probe.redirects",
+            url = url,
+            chain = probe.redirects.join(" → ")
+        );
+        report.push_str(&format!("{redirects}\n"));
+    }
+
+    if let Some(expiry) = probe.tls_expiry {
+        let tls = t!(
+            language,
+            "probe.tls",
+            date = expiry.format("%Y-%m-%d"),
+            days = (expiry - Utc::now()).num_days()
+        );
+        report.push_str(&format!("{tls}\n"));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::ListQuery;
+
+    #[test]
+    fn test_list_escapes_urls() {
+        let websites = [Website {
+            url: "https://example.com/?a=<b>&b=</pre>".to_string(),
+            status: 200,
+            ..Default::default()
+        }];
+        let page = ListQuery::default().page(&websites, Utc::now());
+        let list = format_website_list(Language::En, &page);
+        assert!(list.contains("🟢 | https://example.com/?a=&lt;b&gt;&amp;b=&lt;/pre&gt; | 200"));
+        assert_eq!(list.matches("</pre>").count(), 1);
+    }
+
+    #[test]
+    fn test_columns_fit_content() {
+        let long = format!("https://{}.example.com", "a".repeat(40));
+        let rows = vec![
+            vec!["URL".to_string(), "Status".to_string()],
+            vec![long.clone(), "200".to_string()],
+            vec!["https://b.com".to_string(), "503".to_string()],
+        ];
+        let lines: Vec<String> = table(&rows).lines().map(str::to_string).collect();
+        assert_eq!(lines[2], format!("{long} | 200"));
+        assert_eq!(lines[3].find('|'), lines[2].find('|'));
+        assert_eq!(lines[1].len(), lines[2].len() + 3);
+    }
+
+    #[test]
+    fn test_list_row() {
+        let now = Utc::now();
+        let websites = [Website {
+            url: "https://example.com".to_string(),
+            status: 503,
+            last_updated: now - chrono::Duration::hours(2),
+            last_checked: Some(now - chrono::Duration::minutes(5)),
+            latency_ms: Some(840),
+            ..Default::default()
+        }];
+        let page = ListQuery::default().page(&websites, now);
+        let list = format_website_list(Language::En, &page);
+        assert!(
+            list.contains("🔴 | https://example.com | 503    | down 2h | 5m ago  | 840 ms"),
+            "{list}"
+        );
+    }
+
+    #[test]
+    fn test_emoji_width() {
+        let rows = vec![
+            vec![String::new(), "URL".to_string()],
+            vec!["🟢".to_string(), "a".to_string()],
+        ];
+        assert_eq!(table(&rows).lines().next(), Some("   | URL"));
+    }
 }
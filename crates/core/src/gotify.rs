@@ -0,0 +1,64 @@
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
+
+use crate::channel::{headline, severity, status_line};
+use crate::i18n::Language;
+use crate::mongo::Website;
+
+/// Post to a Gotify server's `/message` endpoint with an application token.
+/// Gotify priorities run from 0 to 10, twice ntfy's scale.
+pub fn request(
+    client: &Client,
+    url: &str,
+    token: &str,
+    website: &Website,
+    language: Language,
+) -> RequestBuilder {
+    client
+        .post(format!("{}/message", url.trim_end_matches('/')))
+        .header("X-Gotify-Key", token)
+        .json(&json!({
+            "title": headline(website, language),
+            "message": status_line(website, language, ""),
+            "priority": severity(website) * 2,
+            "extras": {
+                "client::notification": { "click": { "url": website.url } },
+            },
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let website = Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(200),
+            status: 503,
+            ..Default::default()
+        };
+        let request = request(
+            &Client::new(),
+            "https://gotify.example.org/",
+            "app-token",
+            &website,
+            Language::En,
+        )
+        .build()
+        .unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+
+        assert_eq!(request.url().as_str(), "https://gotify.example.org/message");
+        assert_eq!(request.headers()["X-Gotify-Key"], "app-token");
+        assert_eq!(body["title"], "https://example.com is down");
+        assert_eq!(body["message"], "Status: 503 (was 200)");
+        assert_eq!(body["priority"], 8);
+        assert_eq!(
+            body["extras"]["client::notification"]["click"]["url"],
+            "https://example.com"
+        );
+    }
+}
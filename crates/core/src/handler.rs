@@ -1,23 +1,181 @@
-use crate::alert::process;
-use crate::format::format_website_list;
-use crate::http::HttpClient;
-use crate::mongo::{clear_user_websites, delete_sites_by_hostname, get_user_websites, put_site};
+use crate::alert::{process, render_alert};
+use crate::callback::{Action, Callback, alert_keyboard};
+use crate::channel::{Channel, MAX_CHANNELS};
+use crate::digest::DigestInterval;
+use crate::duration::{format_duration, parse_duration};
+use crate::email::{
+    CODE_TTL_MINUTES, CodeQuota, Email, EmailSubscription, MAX_CODES_PER_HOUR, SmtpConfig,
+    Verification, is_valid_address, send_email,
+};
+use crate::format::{format_deliveries, format_probe_report, format_tags, format_website_list};
+use crate::html::{send_html, strip_tags};
+use crate::http::{HttpClient, probe_site, tracing_client};
+use crate::i18n::{Language, t};
+use crate::incident::track_incident;
+use crate::list::ListQuery;
+use crate::maintenance::MaintenanceWindow;
+use crate::mongo::{
+    ChatSettings, Escalation, Website, acknowledge_incidents, clear_chat_websites, delete_site,
+    delete_sites_by_hostname, get_chat_settings, get_chat_websites, get_site,
+    get_webhook_deliveries, mute_site, pause_sites, put_site, record_checks, resume_sites,
+    set_channels, set_digest, set_email, set_email_quota, set_escalation, set_language,
+    set_maintenance_windows, set_notify, set_quiet_hours, set_tag_notify, set_tags_by_hostname,
+    set_templates, set_webhooks,
+};
+use crate::notify::Notifiers;
 use crate::parse_url::{extract_hostname, read_url};
+use crate::poll::report_changes;
+use crate::settings::{NotifyOn, QuietHours};
+use crate::tag::{SiteSelector, parse_tags};
+use crate::template::{AlertContext, StatusClass, VARIABLES};
+use crate::webhook::{
+    MAX_WEBHOOKS, SIGNATURE_HEADER, TIMESTAMP_HEADER, WebhookTarget, is_valid_webhook_url,
+};
+use chrono::{Duration, Utc};
 use futures::join;
 use mongodb::Collection;
 use mongodb::bson::Document;
 use std::sync::Arc;
-use teloxide::{prelude::*, types::ParseMode};
+use teloxide::{
+    prelude::*,
+    types::{Chat, ParseMode, User},
+    utils::html::escape,
+};
 
-pub async fn handle_about(bot: Bot, msg: Message) -> ResponseResult<()> {
-    let output = "<b>ManDown</b>:
-Open Source on <a href='https://github.com/Donnie/ManDown'>GitHub</a>
-Hosted on GCP in us-east-1
-No personally identifiable information is stored or used by this bot.";
+// Longest a site can be paused for; without a duration it stays paused
+// until resumed
+const MAX_PAUSE_DAYS: i64 = 365;
 
-    bot.send_message(msg.chat.id, output)
-        .parse_mode(ParseMode::Html)
-        .await?;
+// Same timeout the shared client is built with
+const CHECK_TIMEOUT_SECS: u64 = 30;
+
+/// The client `/check` probes with, built once for the dispatcher. A type of
+/// its own keeps it apart from the shared client among the dependencies.
+pub struct CheckClient(reqwest::Client);
+
+impl Default for CheckClient {
+    fn default() -> Self {
+        CheckClient(tracing_client(CHECK_TIMEOUT_SECS))
+    }
+}
+
+// Sites belong to the chat the command was sent in, so a group or channel
+// shares one list and receives its alerts in the same place.
+fn owner_id(msg: &Message) -> i64 {
+    msg.chat.id.0
+}
+
+fn detect_language(user: Option<&User>) -> Language {
+    user.and_then(|user| user.language_code.as_deref())
+        .and_then(Language::parse)
+        .unwrap_or_default()
+}
+
+/// The language to answer a command in. A chat that never chose one takes
+/// the language of the first person to talk to the bot there, so its alerts
+/// match the replies that person saw.
+pub async fn chat_language(collection: &Collection<Document>, msg: &Message) -> Language {
+    let chat_id = owner_id(msg);
+    let chosen = match get_chat_settings(collection, chat_id).await {
+        Ok(chat) => chat.language,
+        Err(e) => {
+            log::error!("Failed to get chat settings: {e}");
+            return detect_language(msg.from());
+        }
+    };
+    if let Some(language) = chosen {
+        return language;
+    }
+
+    let detected = msg
+        .from()
+        .and_then(|user| user.language_code.as_deref())
+        .and_then(Language::parse);
+    if let Some(language) = detected
+        && let Err(e) = set_language(collection, chat_id, language).await
+    {
+        log::error!("Failed to save language: {e}");
+    }
+    detected.unwrap_or_default()
+}
+
+// In groups the list is shared, so only administrators may change it.
+async fn can_manage(bot: &Bot, chat: &Chat, user: Option<&User>) -> ResponseResult<bool> {
+    if !(chat.is_group() || chat.is_supergroup()) {
+        return Ok(true);
+    }
+    let Some(user) = user else {
+        return Ok(false);
+    };
+    let member = bot.get_chat_member(chat.id, user.id).await?;
+    Ok(member.is_privileged())
+}
+
+async fn ensure_admin(bot: &Bot, msg: &Message, language: Language) -> ResponseResult<bool> {
+    // Anonymous administrators post on behalf of the group itself
+    let anonymous_admin = msg.sender_chat().is_some_and(|chat| chat.id == msg.chat.id);
+    if anonymous_admin || can_manage(bot, &msg.chat, msg.from()).await? {
+        return Ok(true);
+    }
+    send_html(bot, msg.chat.id, t!(language, "admin_only")).await?;
+    Ok(false)
+}
+
+pub async fn handle_about(bot: Bot, msg: Message, language: Language) -> ResponseResult<()> {
+    send_html(&bot, msg.chat.id, t!(language, "about")).await?;
+
+    Ok(())
+}
+
+pub async fn handle_ack(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    website: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let website = website.trim();
+    let hostname = extract_hostname(website);
+    if !website.is_empty() && hostname.len() < 3 {
+        send_html(&bot, msg.chat.id, t!(language, "invalid_url")).await?;
+        return Ok(());
+    }
+
+    let message = match acknowledge_incidents(collection, owner_id(&msg), None, &hostname).await {
+        Ok(0) => t!(language, "ack.none"),
+        Ok(count) => t!(language, "ack.done", count = count),
+        Err(e) => {
+            log::error!("Error acknowledging incidents: {e}");
+            t!(language, "ack.error")
+        }
+    };
+
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
+pub async fn handle_check(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    website: String,
+    client: &CheckClient,
+) -> ResponseResult<()> {
+    let (valid, normal, ssl) = read_url(&website);
+    if !valid {
+        send_html(&bot, msg.chat.id, t!(language, "invalid_url")).await?;
+        return Ok(());
+    }
+
+    let client = &client.0;
+    let (normal_probe, ssl_probe) = join!(probe_site(client, &normal), probe_site(client, &ssl));
+    let reports = [
+        format_probe_report(language, &normal, &normal_probe),
+        format_probe_report(language, &ssl, &ssl_probe),
+    ];
+
+    send_html(&bot, msg.chat.id, reports.join("\n")).await?;
 
     Ok(())
 }
@@ -25,51 +183,865 @@ No personally identifiable information is stored or used by this bot.";
 pub async fn handle_clear(
     bot: Bot,
     msg: Message,
+    language: Language,
     collection: &Collection<Document>,
     confirmation: String,
 ) -> ResponseResult<()> {
-    let mut message = r#"
-To clear your entire list of followed domains, please type:
-<pre>
-/clear confirmed
-</pre>
-"#
-    .to_string();
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "{}\n<pre>\n/clear confirmed\n</pre>",
+        t!(language, "clear.usage")
+    );
 
     if confirmation.to_lowercase() == "confirmed" {
-        let telegram_id = msg.from().unwrap().id.0 as i32;
-        message = match clear_user_websites(collection, telegram_id).await {
-            Ok(count) => format!("Successfully cleared {count} site(s)"),
+        let chat_id = owner_id(&msg);
+        message = match clear_chat_websites(collection, chat_id).await {
+            Ok(count) => t!(language, "clear.done", count = count),
             Err(e) => {
                 log::error!("Failed to clear user websites: {e}");
-                format!("Failed to clear user websites: {e}")
+                t!(language, "clear.failed", error = e)
             }
         };
     }
-    bot.send_message(msg.chat.id, message)
-        .parse_mode(ParseMode::Html)
-        .await?;
+    send_html(&bot, msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_email(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let chat_id = owner_id(&msg);
+    let (current, quota) = match get_chat_settings(collection, chat_id).await {
+        Ok(chat) => (chat.email, chat.email_quota),
+        Err(e) => {
+            log::error!("Failed to get chat settings: {e}");
+            send_html(&bot, msg.chat.id, t!(language, "settings.failed_get")).await?;
+            return Ok(());
+        }
+    };
+
+    let args: Vec<&str> = args.split_whitespace().collect();
+    if !args.is_empty() && !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+
+    let usage = format!(
+        "{}\n<pre>\n/email you@example.com\n/email verify &lt;code&gt;\n/email off\n</pre>",
+        t!(language, "email.usage")
+    );
+    let message = match (&args[..], current) {
+        ([], None) => usage,
+        ([], Some(email)) if email.verified => {
+            let active = t!(language, "email.active", address = email.address);
+            format!("{active}\n\n{usage}")
+        }
+        ([], Some(email)) => {
+            let pending = t!(language, "email.pending", address = email.address);
+            format!("{pending}\n\n{usage}")
+        }
+        (["off"], _) => match set_email(collection, chat_id, None).await {
+            Ok(()) => t!(language, "email.off"),
+            Err(e) => {
+                log::error!("Failed to turn off email alerts: {e}");
+                t!(language, "email.off_failed")
+            }
+        },
+        // Wrong guesses count against the code, so it is saved either way
+        (["verify", code], Some(mut email)) if !email.verified => {
+            let outcome = email.verify(code, Utc::now());
+            match (set_email(collection, chat_id, Some(&email)).await, outcome) {
+                (Err(e), _) => {
+                    log::error!("Failed to verify email address: {e}");
+                    t!(language, "email.verify_failed")
+                }
+                (Ok(()), Verification::Verified) => {
+                    t!(language, "email.verified", address = email.address)
+                }
+                (Ok(()), Verification::WrongCode) => t!(language, "email.wrong_code"),
+                (Ok(()), Verification::Expired) => t!(language, "email.code_expired"),
+            }
+        }
+        (["verify", _], _) => t!(language, "email.nothing_pending"),
+        ([address], _) if is_valid_address(address) => {
+            start_email_verification(collection, chat_id, address, language, quota).await
+        }
+        _ => usage,
+    };
+
+    send_html(&bot, msg.chat.id, message).await?;
+    Ok(())
+}
+
+// The address is only stored once the email with its code went out. Each
+// attempt counts towards the hourly limit, whether or not it was delivered.
+async fn start_email_verification(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    address: &str,
+    language: Language,
+    mut quota: CodeQuota,
+) -> String {
+    let smtp = match SmtpConfig::from_env() {
+        Ok(Some(smtp)) => smtp,
+        Ok(None) => return t!(language, "email.not_configured"),
+        Err(e) => {
+            log::error!("Not sending verification email: {e}");
+            return t!(language, "email.not_configured");
+        }
+    };
+    let now = Utc::now();
+    if !quota.take(now) {
+        return t!(language, "email.too_many", max = MAX_CODES_PER_HOUR);
+    }
+    if let Err(e) = set_email_quota(collection, chat_id, &quota).await {
+        log::error!("Failed to save verification email count: {e}");
+        return t!(language, "email.failed_save");
+    }
+    let email = EmailSubscription::pending(address, now);
+    let code = email.code.as_deref().unwrap_or_default();
+    let verification = Email::from_html(
+        address,
+        &t!(language, "email.subject"),
+        &t!(language, "email.body", code = code),
+    );
+
+    if let Err(e) = send_email(&smtp, &verification).await {
+        log::error!("Failed to send verification email: {e}");
+        return t!(language, "email.send_failed", address = address);
+    }
+    match set_email(collection, chat_id, Some(&email)).await {
+        Ok(()) => t!(
+            language,
+            "email.code_sent",
+            address = address,
+            minutes = CODE_TTL_MINUTES
+        ),
+        Err(e) => {
+            log::error!("Failed to save email address: {e}");
+            t!(language, "email.failed_save")
+        }
+    }
+}
+
+pub async fn handle_escalate(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+
+    let usage = format!(
+        "{}\n<pre>\n/escalate &lt;chat id&gt; [30m|2h]\n/escalate off\n</pre>",
+        t!(language, "escalate.usage")
+    );
+    let mut parts = args.split_whitespace();
+    let message = match (parts.next(), parts.next()) {
+        (Some("off"), None) => match set_escalation(collection, owner_id(&msg), None).await {
+            Ok(()) => t!(language, "escalate.off"),
+            Err(e) => {
+                log::error!("Failed to turn off escalation: {e}");
+                t!(language, "escalate.off_failed")
+            }
+        },
+        (Some(target), after) => {
+            let target = target.parse::<i64>().ok().map(ChatId);
+            let after = after.map_or(Some(Duration::hours(1)), parse_duration);
+            match (target, after) {
+                (Some(target), Some(after)) => {
+                    configure_escalation(&bot, &msg, language, collection, target, after).await?
+                }
+                _ => usage,
+            }
+        }
+        (None, _) => usage,
+    };
+
+    send_html(&bot, msg.chat.id, message).await?;
+    Ok(())
+}
+
+// Only let people escalate into chats they administer themselves
+async fn configure_escalation(
+    bot: &Bot,
+    msg: &Message,
+    language: Language,
+    collection: &Collection<Document>,
+    target: ChatId,
+    after: Duration,
+) -> ResponseResult<String> {
+    let allowed = match (msg.from(), bot.get_chat(target).await) {
+        (Some(user), Ok(chat)) => can_manage(bot, &chat, Some(user)).await.unwrap_or(false),
+        _ => false,
+    };
+    if !allowed {
+        return Ok(t!(language, "escalate.not_allowed", target = target));
+    }
+
+    let escalation = Escalation {
+        chat_id: target.0,
+        after_secs: after.num_seconds(),
+    };
+    Ok(
+        match set_escalation(collection, owner_id(msg), Some(&escalation)).await {
+            Ok(()) => t!(
+                language,
+                "escalate.saved",
+                target = target,
+                after = format_duration(after)
+            ),
+            Err(e) => {
+                log::error!("Failed to save escalation: {e}");
+                t!(language, "escalate.failed_save")
+            }
+        },
+    )
+}
+
+pub async fn handle_language(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    code: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let code = code.trim();
+    let usage = format!(
+        "<pre>\n{}\n</pre>",
+        Language::ALL
+            .iter()
+            .map(|language| format!("/language {} – {}", language.code(), language.name()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    if code.is_empty() {
+        let current = t!(language, "language.current", language = language.name());
+        send_html(&bot, msg.chat.id, format!("{current}\n{usage}")).await?;
+        return Ok(());
+    }
+    let Some(chosen) = Language::parse(code) else {
+        let unknown = t!(language, "language.unknown", code = code);
+        send_html(&bot, msg.chat.id, format!("{unknown}\n{usage}")).await?;
+        return Ok(());
+    };
+
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+    let message = match set_language(collection, owner_id(&msg), chosen).await {
+        Ok(()) => t!(chosen, "language.set", language = chosen.name()),
+        Err(e) => {
+            log::error!("Failed to save language: {e}");
+            t!(language, "language.failed_save")
+        }
+    };
+    send_html(&bot, msg.chat.id, message).await?;
+
     Ok(())
 }
 
 pub async fn handle_list(
     bot: Bot,
     msg: Message,
+    language: Language,
+    args: String,
     collection: &Collection<Document>,
 ) -> ResponseResult<()> {
-    let telegram_id = msg.from().unwrap().id.0 as i32;
+    let query = match ListQuery::parse(&args) {
+        Ok(query) => query,
+        Err(e) => {
+            let usage = format!(
+                "{}\n<pre>\n/list down|up|paused\n/list tag:prod\n/list example.com\n/list sort:status|changed\n</pre>",
+                t!(language, "list.usage")
+            );
+            let error = e.message(language);
+            send_html(&bot, msg.chat.id, format!("{error}\n{usage}")).await?;
+            return Ok(());
+        }
+    };
 
-    let message = match get_user_websites(collection, telegram_id).await {
-        Ok(websites) => format_website_list(&websites),
+    let websites = match get_chat_websites(collection, owner_id(&msg)).await {
+        Ok(websites) => websites,
         Err(e) => {
             log::error!("Failed to get user websites: {e}");
-            "Failed to get user websites".to_string()
+            send_html(&bot, msg.chat.id, t!(language, "list.failed")).await?;
+            return Ok(());
         }
     };
 
-    bot.send_message(msg.chat.id, message)
-        .parse_mode(ParseMode::Html)
+    let page = query.page(&websites, Utc::now());
+    let message = format_website_list(language, &page);
+    match query.keyboard(&page, language) {
+        Some(keyboard) => {
+            bot.send_message(msg.chat.id, message)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        None => send_html(&bot, msg.chat.id, message).await?,
+    }
+
+    Ok(())
+}
+
+// Turn the page of a /list reply in place
+async fn show_list_page(
+    bot: &Bot,
+    list: &Message,
+    collection: &Collection<Document>,
+    query: ListQuery,
+) -> ResponseResult<()> {
+    let chat_id = list.chat.id;
+    let language = get_chat_settings(collection, chat_id.0)
+        .await
+        .map(|chat| chat.language.unwrap_or_default())
+        .unwrap_or_default();
+    let websites = match get_chat_websites(collection, chat_id.0).await {
+        Ok(websites) => websites,
+        Err(e) => {
+            log::error!("Failed to get user websites: {e}");
+            return Ok(());
+        }
+    };
+
+    let page = query.page(&websites, Utc::now());
+    let mut edit = bot
+        .edit_message_text(chat_id, list.id, format_website_list(language, &page))
+        .parse_mode(ParseMode::Html);
+    if let Some(keyboard) = query.keyboard(&page, language) {
+        edit = edit.reply_markup(keyboard);
+    }
+    // Editing fails if nothing changed, e.g. on a double tap
+    if let Err(e) = edit.await {
+        log::info!("Failed to show list page: {e}");
+    }
+
+    Ok(())
+}
+
+pub async fn handle_maintenance(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let chat_id = owner_id(&msg);
+    let mut windows = match get_chat_settings(collection, chat_id).await {
+        Ok(chat) => chat.maintenance,
+        Err(e) => {
+            log::error!("Failed to get maintenance windows: {e}");
+            send_html(&bot, msg.chat.id, t!(language, "maintenance.failed_get")).await?;
+            return Ok(());
+        }
+    };
+
+    let (action, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+    let changed = match action.to_lowercase().as_str() {
+        "" | "list" => None,
+        "add" => match MaintenanceWindow::parse(rest) {
+            Ok(window) => {
+                windows.push(window);
+                Some(Ok(t!(language, "maintenance.added")))
+            }
+            Err(e) => Some(Err(e.message(language))),
+        },
+        "remove" => match rest.trim().parse::<usize>() {
+            Ok(n) if (1..=windows.len()).contains(&n) => {
+                windows.remove(n - 1);
+                Some(Ok(t!(language, "maintenance.removed")))
+            }
+            _ => Some(Err(t!(language, "maintenance.missing", n = rest))),
+        },
+        "clear" => {
+            windows.clear();
+            Some(Ok(t!(language, "maintenance.cleared")))
+        }
+        _ => Some(Err(t!(language, "unknown_action", action = action))),
+    };
+
+    let mut message = String::new();
+    match changed {
+        None => {}
+        Some(Err(outcome)) => message = format!("{outcome}\n\n"),
+        Some(Ok(outcome)) => {
+            if !ensure_admin(&bot, &msg, language).await? {
+                return Ok(());
+            }
+            if let Err(e) = set_maintenance_windows(collection, chat_id, &windows).await {
+                log::error!("Failed to save maintenance windows: {e}");
+                send_html(&bot, msg.chat.id, t!(language, "maintenance.failed_save")).await?;
+                return Ok(());
+            }
+            message = format!("{outcome}\n\n");
+        }
+    }
+
+    if windows.is_empty() {
+        message.push_str(&t!(language, "maintenance.none"));
+    } else {
+        message.push_str(&format!("{}\n", t!(language, "maintenance.list")));
+        for (n, window) in windows.iter().enumerate() {
+            message.push_str(&format!(
+                "{}. {}\n",
+                n + 1,
+                escape(&window.describe(language))
+            ));
+        }
+    }
+    message.push_str(
+        r#"
+<pre>
+/maintenance add [site] sun 02:00-04:00 Europe/Berlin [summary]
+/maintenance remove &lt;number&gt;
+/maintenance clear
+</pre>"#,
+    );
+
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
+pub async fn handle_webhook(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let chat_id = owner_id(&msg);
+    let mut webhooks = match get_chat_settings(collection, chat_id).await {
+        Ok(chat) => chat.webhooks,
+        Err(e) => {
+            log::error!("Failed to get webhooks: {e}");
+            send_html(&bot, msg.chat.id, t!(language, "webhook.failed_get")).await?;
+            return Ok(());
+        }
+    };
+
+    let (action, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+    let action = action.to_lowercase();
+    if action == "log" {
+        let message = match get_webhook_deliveries(collection, chat_id, 10).await {
+            Ok(deliveries) => format_deliveries(language, &deliveries),
+            Err(e) => {
+                log::error!("Failed to get webhook deliveries: {e}");
+                t!(language, "webhook.log_failed")
+            }
+        };
+        send_html(&bot, msg.chat.id, message).await?;
+        return Ok(());
+    }
+
+    let rest = rest.trim();
+    let mut private_secret = None;
+    let changed = match action.as_str() {
+        "" | "list" => None,
+        "add" if !is_valid_webhook_url(rest).await => {
+            Some(Err(t!(language, "webhook.invalid", url = rest)))
+        }
+        "add" if webhooks.len() >= MAX_WEBHOOKS => {
+            Some(Err(t!(language, "webhook.limit", max = MAX_WEBHOOKS)))
+        }
+        "add" => {
+            let target = WebhookTarget::new(rest);
+            let added = t!(
+                language,
+                "webhook.added",
+                header = SIGNATURE_HEADER,
+                timestamp = TIMESTAMP_HEADER
+            );
+            let secret = format!("{added}\n<code>{}</code>", target.secret);
+            webhooks.push(target);
+            // Everyone in a group would see the secret, so it goes to
+            // whoever added the webhook instead
+            if msg.chat.is_private() {
+                Some(Ok(secret))
+            } else {
+                private_secret = Some(secret);
+                Some(Ok(t!(language, "webhook.secret_sent")))
+            }
+        }
+        "remove" => match rest.parse::<usize>() {
+            Ok(n) if (1..=webhooks.len()).contains(&n) => {
+                webhooks.remove(n - 1);
+                Some(Ok(t!(language, "webhook.removed")))
+            }
+            _ => Some(Err(t!(language, "webhook.missing", n = rest))),
+        },
+        _ => Some(Err(t!(language, "unknown_action", action = action))),
+    };
+
+    let mut message = String::new();
+    match changed {
+        None => {}
+        Some(Err(outcome)) => message = format!("{outcome}\n\n"),
+        Some(Ok(outcome)) => {
+            if !ensure_admin(&bot, &msg, language).await? {
+                return Ok(());
+            }
+            if let Some(secret) = private_secret
+                && !send_privately(&bot, &msg, secret).await
+            {
+                send_html(&bot, msg.chat.id, t!(language, "webhook.private_failed")).await?;
+                return Ok(());
+            }
+            if let Err(e) = set_webhooks(collection, chat_id, &webhooks).await {
+                log::error!("Failed to save webhooks: {e}");
+                send_html(&bot, msg.chat.id, t!(language, "webhook.failed_save")).await?;
+                return Ok(());
+            }
+            message = format!("{outcome}\n\n");
+        }
+    }
+
+    if webhooks.is_empty() {
+        message.push_str(&t!(language, "webhook.none"));
+    } else {
+        message.push_str(&format!("{}\n", t!(language, "webhook.list")));
+        for (n, webhook) in webhooks.iter().enumerate() {
+            message.push_str(&format!("{}. {}\n", n + 1, escape(&webhook.url)));
+        }
+    }
+    message.push_str(
+        r#"
+<pre>
+/webhook add https://example.com/hook
+/webhook remove &lt;number&gt;
+/webhook log
+</pre>"#,
+    );
+
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
+// Fails if the sender is anonymous or never started a chat with the bot
+async fn send_privately(bot: &Bot, msg: &Message, html: String) -> bool {
+    let Some(user) = msg.from() else {
+        return false;
+    };
+    match send_html(bot, ChatId::from(user.id), html).await {
+        Ok(()) => true,
+        Err(e) => {
+            log::info!("Failed to message {} privately: {e}", user.id);
+            false
+        }
+    }
+}
+
+pub async fn handle_connect(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let chat_id = owner_id(&msg);
+    let mut channels = match get_chat_settings(collection, chat_id).await {
+        Ok(chat) => chat.channels,
+        Err(e) => {
+            log::error!("Failed to get channels: {e}");
+            send_html(&bot, msg.chat.id, t!(language, "connect.failed_get")).await?;
+            return Ok(());
+        }
+    };
+
+    let (action, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+    let action = action.to_lowercase();
+    let rest = rest.trim();
+    let changed = match action.as_str() {
+        "" | "list" => None,
+        "remove" => match rest.parse::<usize>() {
+            Ok(n) if (1..=channels.len()).contains(&n) => {
+                let channel = channels.remove(n - 1);
+                Some(Ok(t!(
+                    language,
+                    "connect.disconnected",
+                    channel = channel.describe()
+                )))
+            }
+            _ => Some(Err(t!(language, "connect.missing", n = rest))),
+        },
+        _ if channels.len() >= MAX_CHANNELS => {
+            Some(Err(t!(language, "connect.limit", max = MAX_CHANNELS)))
+        }
+        _ => match Channel::parse(&args) {
+            Ok(channel) if !channel.is_public().await => Some(Err(t!(
+                language,
+                "connect.not_public",
+                channel = channel.describe()
+            ))),
+            Ok(channel) => {
+                let outcome = t!(language, "connect.connected", channel = channel.describe());
+                channels.push(channel);
+                Some(Ok(outcome))
+            }
+            Err(e) => Some(Err(e.message(language))),
+        },
+    };
+
+    let mut message = String::new();
+    match changed {
+        None => {}
+        Some(Err(outcome)) => message = format!("{outcome}\n\n"),
+        Some(Ok(outcome)) => {
+            if !ensure_admin(&bot, &msg, language).await? {
+                return Ok(());
+            }
+            if let Err(e) = set_channels(collection, chat_id, &channels).await {
+                log::error!("Failed to save channels: {e}");
+                send_html(&bot, msg.chat.id, t!(language, "connect.failed_save")).await?;
+                return Ok(());
+            }
+            message = format!("{outcome}\n\n");
+        }
+    }
+
+    if channels.is_empty() {
+        message.push_str(&t!(language, "connect.none"));
+    } else {
+        message.push_str(&format!("{}\n", t!(language, "connect.list")));
+        for (n, channel) in channels.iter().enumerate() {
+            message.push_str(&format!("{}. {}\n", n + 1, escape(&channel.describe())));
+        }
+    }
+    message.push_str(
+        r#"
+<pre>
+/connect slack https://hooks.slack.com/services/...
+/connect discord https://discord.com/api/webhooks/...
+/connect ntfy https://ntfy.sh/&lt;topic&gt; [token]
+/connect gotify https://gotify.example.com &lt;app token&gt;
+/connect matrix https://matrix.example.org !room:example.org &lt;access token&gt;
+/connect remove &lt;number&gt;
+</pre>"#,
+    );
+
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
+pub async fn handle_template(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let chat_id = owner_id(&msg);
+    let mut chat = match get_chat_settings(collection, chat_id).await {
+        Ok(chat) => chat,
+        Err(e) => {
+            log::error!("Failed to get chat settings: {e}");
+            send_html(&bot, msg.chat.id, t!(language, "template.failed_get")).await?;
+            return Ok(());
+        }
+    };
+
+    // The template itself may span several lines, so only the class is split off
+    let args = args.trim();
+    let (name, template) = args
+        .split_once(char::is_whitespace)
+        .map_or((args, ""), |(name, template)| (name, template.trim()));
+    let class = match StatusClass::parse(name) {
+        Some(class) => class,
+        None if name.is_empty() => {
+            let mut message = format!("<b>{}</b>\n", t!(language, "template.title"));
+            for class in StatusClass::ALL {
+                let state = match chat.templates.get(class) {
+                    Some(_) => t!(language, "template.custom"),
+                    None => t!(language, "template.builtin"),
+                };
+                message.push_str(&format!("{}: {state}\n", class.name()));
+            }
+            message.push_str(&template_usage(language));
+            send_html(&bot, msg.chat.id, message).await?;
+            return Ok(());
+        }
+        None => {
+            send_html(
+                &bot,
+                msg.chat.id,
+                format!(
+                    "{}{}",
+                    t!(language, "template.unknown", name = name),
+                    template_usage(language)
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if template.is_empty() {
+        let preview = chat.templates.render(
+            &AlertContext::new("https://example.com", class.example_status()),
+            language,
+        );
+        let message = match chat.templates.get(class) {
+            Some(template) => format!("<pre>{}</pre>\n\n{preview}", escape(template)),
+            None => format!("{}\n\n{preview}", t!(language, "template.using_builtin")),
+        };
+        send_html(&bot, msg.chat.id, message).await?;
+        return Ok(());
+    }
+
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+    let reset = template.eq_ignore_ascii_case("reset");
+    if let Err(e) = chat.templates.set(class, (!reset).then_some(template)) {
+        send_html(
+            &bot,
+            msg.chat.id,
+            t!(language, "template.invalid", error = e),
+        )
         .await?;
+        return Ok(());
+    }
+    if let Err(e) = set_templates(collection, chat_id, &chat.templates).await {
+        log::error!("Failed to save templates: {e}");
+        send_html(&bot, msg.chat.id, t!(language, "template.failed_save")).await?;
+        return Ok(());
+    }
+
+    let key = if reset {
+        "template.reset"
+    } else {
+        "template.saved"
+    };
+    let outcome = t!(language, key, class = class.name());
+    let preview = chat.templates.render(
+        &AlertContext::new("https://example.com", class.example_status()),
+        language,
+    );
+    send_html(&bot, msg.chat.id, format!("{outcome}\n\n{preview}")).await?;
+
+    Ok(())
+}
+
+fn template_usage(language: Language) -> String {
+    let classes: Vec<&str> = StatusClass::ALL.iter().map(|class| class.name()).collect();
+    let variables: Vec<String> = VARIABLES
+        .iter()
+        .map(|name| format!("{{{{{name}}}}}"))
+        .collect();
+    format!(
+        r#"
+<pre>
+/template &lt;class&gt; &lt;template&gt;
+/template &lt;class&gt; reset
+/template &lt;class&gt;
+</pre>
+{}: {}
+{}: {}
+{}"#,
+        t!(language, "template.classes"),
+        classes.join(", "),
+        t!(language, "template.variables"),
+        variables.join(" "),
+        t!(language, "template.if_help")
+    )
+}
+
+pub async fn handle_pause(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let usage = format!(
+        "{}\n<pre>\n/pause google.in\n/pause google.in 2h\n/pause tag:staging\n</pre>",
+        t!(language, "pause.usage")
+    );
+    let mut parts = args.split_whitespace();
+    let Some(selector) = parts.next().and_then(SiteSelector::parse) else {
+        send_html(&bot, msg.chat.id, usage).await?;
+        return Ok(());
+    };
+    let duration = parts
+        .next()
+        .map(|arg| parse_duration(arg).filter(|d| *d <= Duration::days(MAX_PAUSE_DAYS)));
+    let until = duration
+        .flatten()
+        .and_then(|d| Utc::now().checked_add_signed(d));
+    if duration.is_some() && until.is_none() {
+        let error = t!(
+            language,
+            "pause.invalid_duration",
+            max = format!("{MAX_PAUSE_DAYS}d")
+        );
+        send_html(&bot, msg.chat.id, format!("{error}\n{usage}")).await?;
+        return Ok(());
+    }
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+
+    let duration = duration.flatten();
+    let result = pause_sites(collection, &selector, owner_id(&msg), until).await;
+
+    let message = match (result, duration) {
+        (Ok(0), _) => t!(language, "sites.none", hostname = selector),
+        (Ok(count), Some(d)) => t!(
+            language,
+            "pause.until",
+            count = count,
+            hostname = selector,
+            duration = format_duration(d)
+        ),
+        (Ok(count), None) => t!(language, "pause.done", count = count, hostname = selector),
+        (Err(e), _) => {
+            log::error!("Error pausing {selector}: {e}");
+            t!(language, "pause.error", hostname = selector)
+        }
+    };
+
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
+pub async fn handle_resume(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    website: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let Some(selector) = SiteSelector::parse(website.trim()) else {
+        send_html(&bot, msg.chat.id, t!(language, "invalid_url")).await?;
+        return Ok(());
+    };
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+
+    let message = match resume_sites(collection, &selector, owner_id(&msg)).await {
+        Ok(0) => t!(language, "resume.none", hostname = selector),
+        Ok(count) => t!(language, "resume.done", count = count, hostname = selector),
+        Err(e) => {
+            log::error!("Error resuming {selector}: {e}");
+            t!(language, "resume.error", hostname = selector)
+        }
+    };
+
+    send_html(&bot, msg.chat.id, message).await?;
 
     Ok(())
 }
@@ -77,50 +1049,286 @@ pub async fn handle_list(
 async fn check_and_track_url(
     url: &str,
     collection: &Collection<Document>,
-    telegram_id: i32,
+    chat_id: i64,
     client: &reqwest::Client,
+    language: Language,
 ) -> String {
     let status = client.get_status_code(url).await;
-    let mut message = process(url, status as i32);
+    let mut message = process(language, url, status as i32);
 
     if status == 200
-        && let Err(e) = put_site(collection, url, telegram_id).await
+        && let Err(e) = put_site(collection, url, chat_id).await
     {
         log::error!("Failed to insert site {url}: {e}");
-        message = format!("Failed to track <code>{url}</code>");
+        message = t!(language, "track.failed", url = url);
     }
 
     message
 }
 
+pub async fn handle_settings(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let chat_id = owner_id(&msg);
+    let mut chat = match get_chat_settings(collection, chat_id).await {
+        Ok(chat) => chat,
+        Err(e) => {
+            log::error!("Failed to get chat settings: {e}");
+            send_html(&bot, msg.chat.id, t!(language, "settings.failed_get")).await?;
+            return Ok(());
+        }
+    };
+
+    let args: Vec<&str> = args.split_whitespace().collect();
+    if !args.is_empty() && !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+
+    let outcome = match args[..] {
+        [] => None,
+        ["quiet", "off"] => Some(set_quiet_hours(collection, chat_id, None).await.map(|()| {
+            chat.quiet_hours = None;
+            t!(language, "settings.quiet_off")
+        })),
+        ["quiet", ..] => Some(match QuietHours::parse(&args[1..].join(" ")) {
+            Ok(quiet) => set_quiet_hours(collection, chat_id, Some(&quiet))
+                .await
+                .map(|()| {
+                    let message = t!(language, "settings.quiet_set", quiet = quiet.describe());
+                    chat.quiet_hours = Some(quiet);
+                    message
+                }),
+            Err(e) => Ok(e.message(language)),
+        }),
+        ["notify", kind, state @ ("on" | "off"), tag] if tag.starts_with("tag:") => {
+            Some(match SiteSelector::parse(tag) {
+                Some(SiteSelector::Tag(tag)) => {
+                    let mut notify = chat.tag_notify.get(&tag).copied().unwrap_or(chat.notify);
+                    match notify.set(&kind.to_lowercase(), state == "on") {
+                        Ok(()) => {
+                            chat.tag_notify.insert(tag.clone(), notify);
+                            set_tag_notify(collection, chat_id, &chat.tag_notify)
+                                .await
+                                .map(|()| {
+                                    t!(
+                                        language,
+                                        "settings.tag_notify_set",
+                                        kind = kind,
+                                        tag = tag,
+                                        state = t!(language, state)
+                                    )
+                                })
+                        }
+                        Err(e) => Ok(e.message(language)),
+                    }
+                }
+                _ => Ok(t!(language, "settings.unknown")),
+            })
+        }
+        ["notify", tag, "reset"] => Some(match SiteSelector::parse(tag) {
+            Some(SiteSelector::Tag(tag)) if chat.tag_notify.remove(&tag).is_some() => {
+                set_tag_notify(collection, chat_id, &chat.tag_notify)
+                    .await
+                    .map(|()| t!(language, "settings.tag_notify_reset", tag = tag))
+            }
+            _ => Ok(t!(language, "settings.unknown")),
+        }),
+        ["notify", kind, state @ ("on" | "off")] => {
+            Some(match chat.notify.set(&kind.to_lowercase(), state == "on") {
+                Ok(()) => set_notify(collection, chat_id, &chat.notify)
+                    .await
+                    .map(|()| {
+                        t!(
+                            language,
+                            "settings.notify_set",
+                            kind = kind,
+                            state = t!(language, state)
+                        )
+                    }),
+                Err(e) => Ok(e.message(language)),
+            })
+        }
+        ["digest", "off"] => Some(set_digest(collection, chat_id, None).await.map(|()| {
+            chat.digest = None;
+            t!(language, "settings.digest_off")
+        })),
+        ["digest", interval] => Some(match DigestInterval::parse(interval) {
+            Some(interval) => set_digest(collection, chat_id, Some(interval))
+                .await
+                .map(|()| {
+                    chat.digest = Some(interval);
+                    t!(
+                        language,
+                        "settings.digest_set",
+                        interval = t!(language, &format!("digest.{}", interval.name()))
+                    )
+                }),
+            None => Ok(t!(language, "settings.digest_unknown", interval = interval)),
+        }),
+        _ => Some(Ok(t!(language, "settings.unknown"))),
+    };
+
+    let mut message = match outcome {
+        None => String::new(),
+        Some(Ok(outcome)) => format!("{outcome}\n\n"),
+        Some(Err(e)) => {
+            log::error!("Failed to save chat settings: {e}");
+            send_html(&bot, msg.chat.id, t!(language, "settings.failed_save")).await?;
+            return Ok(());
+        }
+    };
+
+    let on_off = |enabled: bool| t!(language, if enabled { "on" } else { "off" });
+    let quiet = chat
+        .quiet_hours
+        .as_ref()
+        .map_or(t!(language, "off"), QuietHours::describe);
+    let digest = chat.digest.map_or(t!(language, "off"), |interval| {
+        t!(language, &format!("digest.{}", interval.name()))
+    });
+    message.push_str(&t!(
+        language,
+        "settings.summary",
+        quiet = quiet,
+        digest = digest,
+        down = on_off(chat.notify.down),
+        recovery = on_off(chat.notify.recovery),
+        change = on_off(chat.notify.status_change),
+        language = language.name()
+    ));
+    for (tag, notify) in &chat.tag_notify {
+        message.push('\n');
+        message.push_str(&tag_notify_line(language, tag, notify));
+    }
+    message.push_str(
+        r#"
+<pre>
+/settings quiet 22:00-07:00 Europe/Berlin
+/settings quiet off
+/settings notify down|recovery|change on|off
+/settings notify down|recovery|change on|off tag:staging
+/settings notify tag:staging reset
+/settings digest hourly|daily|off
+/language en|de
+</pre>"#,
+    );
+
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
+fn tag_notify_line(language: Language, tag: &str, notify: &NotifyOn) -> String {
+    let on_off = |enabled: bool| t!(language, if enabled { "on" } else { "off" });
+    t!(
+        language,
+        "settings.tag_notify",
+        tag = tag,
+        down = on_off(notify.down),
+        recovery = on_off(notify.recovery),
+        change = on_off(notify.status_change)
+    )
+}
+
+pub async fn handle_tag(
+    bot: Bot,
+    msg: Message,
+    language: Language,
+    args: String,
+    collection: &Collection<Document>,
+) -> ResponseResult<()> {
+    let usage = format!(
+        "{}\n<pre>\n/tag example.com prod api\n/tag example.com none\n/list tag:prod\n/pause tag:prod\n</pre>",
+        t!(language, "tag.usage")
+    );
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let Some((site, words)) = args.split_first() else {
+        let message = match get_chat_websites(collection, owner_id(&msg)).await {
+            Ok(websites) => format!("{}\n\n{usage}", format_tags(language, &websites)),
+            Err(e) => {
+                log::error!("Failed to get user websites: {e}");
+                t!(language, "list.failed")
+            }
+        };
+        send_html(&bot, msg.chat.id, message).await?;
+        return Ok(());
+    };
+
+    let hostname = extract_hostname(site);
+    if hostname.len() < 3 || words.is_empty() {
+        send_html(&bot, msg.chat.id, usage).await?;
+        return Ok(());
+    }
+    let tags = match words {
+        ["none"] => Vec::new(),
+        _ => match parse_tags(words) {
+            Ok(tags) => tags,
+            Err(e) => {
+                let error = e.message(language);
+                send_html(&bot, msg.chat.id, format!("{error}\n{usage}")).await?;
+                return Ok(());
+            }
+        },
+    };
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+
+    let result = set_tags_by_hostname(collection, &hostname, owner_id(&msg), &tags).await;
+    let message = match result {
+        Ok(0) => t!(language, "sites.none", hostname = hostname),
+        Ok(count) if tags.is_empty() => {
+            t!(language, "tag.cleared", count = count, hostname = hostname)
+        }
+        Ok(count) => t!(
+            language,
+            "tag.set",
+            count = count,
+            hostname = hostname,
+            tags = tags.join(", ")
+        ),
+        Err(e) => {
+            log::error!("Error tagging {hostname}: {e}");
+            t!(language, "tag.error", hostname = hostname)
+        }
+    };
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
 pub async fn handle_track(
     bot: Bot,
     msg: Message,
+    language: Language,
     website: String,
     collection: &Collection<Document>,
     client: Arc<reqwest::Client>,
 ) -> ResponseResult<()> {
-    let telegram_id = msg.from().unwrap().id.0 as i32;
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+    let chat_id = owner_id(&msg);
 
     let (valid, normal, ssl) = read_url(&website);
     if !valid {
-        bot.send_message(msg.chat.id, "Invalid URL!".to_string())
-            .parse_mode(ParseMode::Html)
-            .await?;
+        send_html(&bot, msg.chat.id, t!(language, "invalid_url")).await?;
         return Ok(());
     }
 
-    let normal_check = check_and_track_url(&normal, collection, telegram_id, &client);
-    let ssl_check = check_and_track_url(&ssl, collection, telegram_id, &client);
+    let normal_check = check_and_track_url(&normal, collection, chat_id, &client, language);
+    let ssl_check = check_and_track_url(&ssl, collection, chat_id, &client, language);
 
     let (normal_result, ssl_result) = join!(normal_check, ssl_check);
 
     let messages = [normal_result, ssl_result];
 
     if !messages.is_empty() {
-        bot.send_message(msg.chat.id, messages.join("\n\n"))
-            .parse_mode(ParseMode::Html)
-            .await?;
+        send_html(&bot, msg.chat.id, messages.join("\n\n")).await?;
     }
 
     Ok(())
@@ -129,32 +1337,186 @@ pub async fn handle_track(
 pub async fn handle_untrack(
     bot: Bot,
     msg: Message,
+    language: Language,
     website: String,
     collection: &Collection<Document>,
 ) -> ResponseResult<()> {
-    let telegram_id = msg.from().unwrap().id.0 as i32;
+    if !ensure_admin(&bot, &msg, language).await? {
+        return Ok(());
+    }
+    let chat_id = owner_id(&msg);
     let hostname = extract_hostname(&website);
     if hostname.len() < 3 {
-        bot.send_message(msg.chat.id, "Invalid URL!".to_string())
-            .parse_mode(ParseMode::Html)
-            .await?;
+        send_html(&bot, msg.chat.id, t!(language, "invalid_url")).await?;
         return Ok(());
     }
 
-    let result = delete_sites_by_hostname(collection, &hostname, telegram_id).await;
+    let result = delete_sites_by_hostname(collection, &hostname, chat_id).await;
 
     let message = match result {
-        Ok(0) => format!("No sites found for {hostname}"),
-        Ok(count) => format!("Successfully untracked {count} site(s) for {hostname}"),
+        Ok(0) => t!(language, "sites.none", hostname = hostname),
+        Ok(count) => t!(language, "untrack.done", count = count, hostname = hostname),
         Err(e) => {
             log::error!("Error untracking {hostname}: {e}");
-            format!("An error occurred while untracking {hostname}")
+            t!(language, "untrack.error", hostname = hostname)
         }
     };
 
-    bot.send_message(msg.chat.id, message)
-        .parse_mode(ParseMode::Html)
+    send_html(&bot, msg.chat.id, message).await?;
+
+    Ok(())
+}
+
+pub async fn handle_callback(
+    bot: Bot,
+    query: CallbackQuery,
+    collection: &Collection<Document>,
+    client: Arc<reqwest::Client>,
+) -> ResponseResult<()> {
+    if let (Some(list), Some(message)) = (
+        query.data.as_deref().and_then(ListQuery::decode),
+        query.message.as_ref(),
+    ) {
+        show_list_page(&bot, message, collection, list).await?;
+        bot.answer_callback_query(query.id).await?;
+        return Ok(());
+    }
+
+    let callback = query.data.as_deref().and_then(Callback::decode);
+    let (Some(callback), Some(alert)) = (callback, query.message.as_ref()) else {
+        bot.answer_callback_query(query.id).await?;
+        return Ok(());
+    };
+    let chat = get_chat_settings(collection, alert.chat.id.0)
+        .await
+        .unwrap_or_default();
+    let language = chat
+        .language
+        .unwrap_or_else(|| detect_language(Some(&query.from)));
+
+    // Anyone in the chat may re-check or acknowledge, as on-call engineers
+    // are not necessarily administrators
+    let needs_admin = matches!(callback.action, Action::Mute | Action::Untrack);
+    if needs_admin && !can_manage(&bot, &alert.chat, Some(&query.from)).await? {
+        bot.answer_callback_query(query.id)
+            .text(t!(language, "callback.admin_only"))
+            .await?;
+        return Ok(());
+    }
+
+    let website = match get_site(collection, callback.site, alert.chat.id.0).await {
+        Ok(Some(website)) => website,
+        Ok(None) => {
+            bot.answer_callback_query(query.id)
+                .text(t!(language, "callback.gone"))
+                .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            log::error!("Failed to load site {}: {e}", callback.site);
+            bot.answer_callback_query(query.id)
+                .text(t!(language, "callback.error"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let name = query.from.full_name();
+    let url = &website.url;
+    let notice = match callback.action {
+        Action::Recheck => {
+            if let Err(e) = recheck(&bot, alert, collection, client, &chat, website.clone()).await {
+                log::error!("Failed to re-check {url}: {e}");
+            }
+            t!(language, "callback.rechecked", url = url)
+        }
+        Action::Mute => {
+            let until = Utc::now() + Duration::hours(1);
+            match mute_site(collection, callback.site, alert.chat.id.0, until).await {
+                Ok(_) => t!(language, "callback.muted", name = name, url = url),
+                Err(e) => {
+                    log::error!("Failed to mute {url}: {e}");
+                    t!(language, "callback.mute_failed", url = url)
+                }
+            }
+        }
+        Action::Acknowledge => {
+            match acknowledge_incidents(collection, alert.chat.id.0, Some(callback.site), "").await
+            {
+                Ok(0) => t!(language, "callback.no_incident", url = url),
+                Ok(_) => t!(language, "callback.acknowledged", name = name, url = url),
+                Err(e) => {
+                    log::error!("Failed to acknowledge {url}: {e}");
+                    t!(language, "callback.ack_failed", url = url)
+                }
+            }
+        }
+        Action::Untrack => match delete_site(collection, callback.site, alert.chat.id.0).await {
+            Ok(_) => {
+                // The alert may be too old to edit, which must not keep the
+                // button spinning
+                if let Err(e) = bot.edit_message_reply_markup(alert.chat.id, alert.id).await {
+                    log::info!("Failed to remove the buttons of an alert: {e}");
+                }
+                t!(language, "callback.untracked", name = name, url = url)
+            }
+            Err(e) => {
+                log::error!("Failed to untrack {url}: {e}");
+                t!(language, "callback.untrack_failed", url = url)
+            }
+        },
+    };
+
+    bot.answer_callback_query(query.id)
+        .text(strip_tags(&notice))
         .await?;
+    if callback.action != Action::Recheck {
+        bot.send_message(alert.chat.id, notice)
+            .parse_mode(ParseMode::Html)
+            .reply_to_message_id(alert.id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+// Probe the site again and reply with the result. A changed status goes
+// through the same path as in a sweep, so the chat's other channels hear of
+// it and the next sweep does not alert on it a second time.
+async fn recheck(
+    bot: &Bot,
+    alert: &Message,
+    collection: &Collection<Document>,
+    client: Arc<reqwest::Client>,
+    chat: &ChatSettings,
+    mut website: Website,
+) -> ResponseResult<()> {
+    let probe = probe_site(&client, &website.url).await;
+    website.record_probe(&probe, Utc::now());
+    if let Err(e) = record_checks(collection, std::slice::from_ref(&website)).await {
+        log::error!("Failed to save check of {}: {e}", website.url);
+    }
+    let status = probe.status as i32;
+    if status != website.status {
+        let now = Utc::now();
+        website.previous_status = Some(website.status);
+        website.status = status;
+        website.failure = probe.failure;
+        website.last_updated = now;
+        let notifiers = Notifiers::external(collection, client);
+        report_changes(collection, &notifiers, std::slice::from_ref(&website)).await;
+        notifiers.finish().await;
+        track_incident(&mut website, now);
+    }
+
+    let mut reply = bot
+        .send_message(alert.chat.id, render_alert(chat, &website))
+        .parse_mode(ParseMode::Html)
+        .reply_to_message_id(alert.id);
+    if let Some(id) = website.id {
+        reply = reply.reply_markup(alert_keyboard(id, chat.language.unwrap_or_default()));
+    }
+    reply.await?;
 
     Ok(())
 }
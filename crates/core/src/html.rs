@@ -0,0 +1,309 @@
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+/// Telegram rejects messages longer than this, counted in UTF-16 code units.
+pub const MAX_MESSAGE_LEN: usize = 4096;
+
+// The raw HTML is never shorter than the text Telegram counts, so measuring
+// it keeps every part within the limit.
+fn len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// A tag, an entity such as `&amp;`, or a single character; messages are
+/// never split inside one.
+fn atoms(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let c = rest.chars().next()?;
+        let end = match c {
+            '<' => rest.find('>').map(|end| end + 1),
+            '&' => rest
+                .char_indices()
+                .take(10)
+                .find(|(_, c)| *c == ';')
+                .map(|(end, _)| end + 1),
+            _ => None,
+        }
+        .unwrap_or(c.len_utf8());
+        let (atom, tail) = rest.split_at(end);
+        rest = tail;
+        Some(atom)
+    })
+}
+
+/// Tags Telegram understands in HTML messages.
+const TAGS: [&str; 16] = [
+    "b",
+    "strong",
+    "i",
+    "em",
+    "u",
+    "ins",
+    "s",
+    "strike",
+    "del",
+    "span",
+    "tg-spoiler",
+    "a",
+    "code",
+    "pre",
+    "blockquote",
+    "tg-emoji",
+];
+
+/// Check that HTML only uses tags Telegram understands, closes them in
+/// order, and writes a literal `<` or `&` as an entity, since Telegram
+/// refuses the whole message otherwise.
+pub fn check_html(html: &str) -> Result<(), String> {
+    let mut open = Vec::new();
+    for atom in atoms(html) {
+        if let Some(tag) = atom.strip_prefix('<') {
+            let Some(tag) = tag.strip_suffix('>') else {
+                return Err("A < is never closed, write &lt; for a literal <".to_string());
+            };
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = name.trim().to_lowercase();
+                if open.pop().as_ref() != Some(&name) {
+                    return Err(format!("</{name}> does not close the last open tag"));
+                }
+                continue;
+            }
+            let name = tag
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            if !TAGS.contains(&name.as_str()) {
+                return Err(format!("<{name}> is not supported by Telegram"));
+            }
+            open.push(name);
+        } else if let Some(entity) = atom.strip_prefix('&') {
+            let entity = entity.strip_suffix(';').unwrap_or_default();
+            let numeric = entity
+                .strip_prefix('#')
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+            if !numeric && !["lt", "gt", "amp", "quot"].contains(&entity) {
+                return Err("Write &amp; for a literal &".to_string());
+            }
+        }
+    }
+    match open.last() {
+        Some(name) => Err(format!("<{name}> is never closed")),
+        None => Ok(()),
+    }
+}
+
+/// Splits HTML into parts that fit a message. Tags still open at a break
+/// are closed at the end of the part and opened again in the next one.
+struct Splitter {
+    limit: usize,
+    parts: Vec<String>,
+    current: String,
+    // Length of the tags reopened at the start of `current`
+    reopened: usize,
+    // Name and full opening tag of each open element
+    open: Vec<(String, String)>,
+}
+
+impl Splitter {
+    fn new(limit: usize) -> Self {
+        Splitter {
+            limit,
+            parts: Vec::new(),
+            current: String::new(),
+            reopened: 0,
+            open: Vec::new(),
+        }
+    }
+
+    fn track(open: &mut Vec<(String, String)>, text: &str) {
+        for tag in atoms(text).filter(|atom| atom.starts_with('<') && atom.ends_with('>')) {
+            if let Some(name) = tag.strip_prefix("</") {
+                let name = name.trim_end_matches('>').trim();
+                if let Some(n) = open.iter().rposition(|(open, _)| open == name) {
+                    open.truncate(n);
+                }
+            } else if !tag.ends_with("/>") {
+                let name = tag[1..tag.len() - 1]
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default();
+                open.push((name.to_string(), tag.to_string()));
+            }
+        }
+    }
+
+    fn closing(open: &[(String, String)]) -> String {
+        open.iter()
+            .rev()
+            .map(|(name, _)| format!("</{name}>"))
+            .collect()
+    }
+
+    fn fits(&self, piece: &str) -> bool {
+        let mut open = self.open.clone();
+        Self::track(&mut open, piece);
+        len(&self.current) + len(piece) + len(&Self::closing(&open)) <= self.limit
+    }
+
+    fn has_content(&self) -> bool {
+        len(&self.current) > self.reopened
+    }
+
+    fn push(&mut self, piece: &str) {
+        self.current.push_str(piece);
+        Self::track(&mut self.open, piece);
+    }
+
+    fn flush(&mut self) {
+        let reopen: String = self.open.iter().map(|(_, tag)| tag.as_str()).collect();
+        let mut part = std::mem::replace(&mut self.current, reopen);
+        part.push_str(&Self::closing(&self.open));
+        self.parts.push(part);
+        self.reopened = len(&self.current);
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        if self.has_content() || self.parts.is_empty() {
+            self.flush();
+        }
+        self.parts
+    }
+}
+
+/// Split an HTML message into parts of at most `limit` UTF-16 code units,
+/// preferably at line breaks. Every part is valid HTML on its own.
+pub fn split_message(html: &str, limit: usize) -> Vec<String> {
+    let mut splitter = Splitter::new(limit);
+    for line in html.split_inclusive('\n') {
+        if !splitter.fits(line) && splitter.has_content() {
+            splitter.flush();
+        }
+        if splitter.fits(line) {
+            splitter.push(line);
+            continue;
+        }
+        // A single line longer than a message
+        for atom in atoms(line) {
+            if !splitter.fits(atom) && splitter.has_content() {
+                splitter.flush();
+            }
+            splitter.push(atom);
+        }
+    }
+    splitter.finish()
+}
+
+/// Plain-text version of the HTML used in Telegram messages.
+pub fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Reply with an HTML message, split into several if it is too long.
+pub async fn send_html(bot: &Bot, chat_id: ChatId, html: String) -> ResponseResult<()> {
+    for part in split_message(&html, MAX_MESSAGE_LEN) {
+        bot.send_message(chat_id, part)
+            .parse_mode(ParseMode::Html)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_html() {
+        for html in [
+            "<b>bold</b> <a href='https://a.com/?a=1&amp;b=2'>link</a>",
+            "<pre><code>x &lt; 1</code></pre>",
+            "&#128293; 5 &gt; 4",
+        ] {
+            assert!(check_html(html).is_ok(), "{html}");
+        }
+        for html in [
+            "<b>bold",
+            "<b><i>x</b></i>",
+            "</b>",
+            "<div>x</div>",
+            "a & b",
+            "a < b",
+            "&nbsp;",
+        ] {
+            assert!(check_html(html).is_err(), "{html}");
+        }
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        assert_eq!(strip_tags("<b>up</b> &amp; running"), "up & running");
+        assert_eq!(strip_tags("a &lt;b&gt;"), "a <b>");
+    }
+
+    #[test]
+    fn test_short_message_is_one_part() {
+        assert_eq!(split_message("<b>hi</b>", 4096), vec!["<b>hi</b>"]);
+        assert_eq!(split_message("", 4096), vec![""]);
+    }
+
+    #[test]
+    fn test_splits_at_lines() {
+        let html = "aaaa\nbbbb\ncccc\n";
+        assert_eq!(split_message(html, 10), vec!["aaaa\nbbbb\n", "cccc\n"]);
+    }
+
+    #[test]
+    fn test_reopens_tags() {
+        let rows: String = (0..100).map(|n| format!("row {n:<20}\n")).collect();
+        let html = format!("<b>Title</b>\n\n<pre>{rows}</pre>");
+        let parts = split_message(&html, 500);
+
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(len(part) <= 500, "{part}");
+            assert_eq!(
+                part.matches("<pre>").count(),
+                part.matches("</pre>").count()
+            );
+        }
+        assert!(parts[1].starts_with("<pre>"));
+        let text: String = parts.iter().map(|part| strip_tags(part)).collect();
+        assert_eq!(text, strip_tags(&html));
+    }
+
+    #[test]
+    fn test_long_line_keeps_entities_whole() {
+        let html = "&lt;script&gt;".repeat(1000);
+        let parts = split_message(&html, 4096);
+
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(len(part) <= 4096);
+            assert_eq!(part.matches('&').count(), part.matches(';').count());
+        }
+        assert_eq!(parts.concat(), html);
+    }
+
+    #[test]
+    fn test_counts_utf16() {
+        // Each emoji takes two UTF-16 code units
+        let parts = split_message(&"🔥".repeat(3000), 4096);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].chars().count(), 2048);
+    }
+}
@@ -1,13 +1,113 @@
+use crate::i18n::{Language, tr};
 use crate::mongo::Website;
 use chrono::{DateTime, Utc};
-use reqwest::Client;
-use std::{sync::Arc, time::SystemTime};
+use reqwest::{Client, Response, header::LOCATION, redirect::Policy, tls::TlsInfo};
+use serde::{Deserialize, Serialize};
+use std::error::Error as _;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Same limit reqwest applies when it follows redirects itself
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    InvalidUrl,
+    Dns,
+    Connect,
+    Tls,
+    Timeout,
+    TooManyRedirects,
+    Request,
+}
+
+impl FailureKind {
+    fn name(self) -> &'static str {
+        match self {
+            FailureKind::InvalidUrl => "invalid_url",
+            FailureKind::Dns => "dns",
+            FailureKind::Connect => "connect",
+            FailureKind::Tls => "tls",
+            FailureKind::Timeout => "timeout",
+            FailureKind::TooManyRedirects => "too_many_redirects",
+            FailureKind::Request => "request",
+        }
+    }
+
+    /// The reason in words, e.g. `timed out`.
+    pub fn describe(self, language: Language) -> &'static str {
+        tr(language, &format!("failure.{}", self.name()))
+    }
+}
+
+// Logs are in English
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.describe(Language::En))
+    }
+}
+
+impl From<&reqwest::Error> for FailureKind {
+    fn from(error: &reqwest::Error) -> Self {
+        if error.is_builder() {
+            return FailureKind::InvalidUrl;
+        }
+        if error.is_timeout() {
+            return FailureKind::Timeout;
+        }
+        if error.is_redirect() {
+            return FailureKind::TooManyRedirects;
+        }
+        if !error.is_connect() {
+            return FailureKind::Request;
+        }
+
+        // Connect errors wrap the resolver or TLS failure that caused them
+        let mut cause = error.source();
+        while let Some(inner) = cause {
+            let text = inner.to_string().to_lowercase();
+            if text.contains("dns") {
+                return FailureKind::Dns;
+            }
+            if text.contains("certificate") || text.contains("tls") || text.contains("ssl") {
+                return FailureKind::Tls;
+            }
+            cause = inner.source();
+        }
+        FailureKind::Connect
+    }
+}
+
+/// Outcome of a single check. `status` is 0 whenever no final response was
+/// received, in which case `failure` says why.
+#[derive(Debug, Clone, Default)]
+pub struct Probe {
+    pub status: u16,
+    pub latency: Duration,
+    pub failure: Option<FailureKind>,
+    pub redirects: Vec<String>,
+    pub tls_expiry: Option<DateTime<Utc>>,
+}
 
 // Trait for HTTP clients to enable testing
 #[async_trait::async_trait]
 pub trait HttpClient: Clone + Send + Sync {
     async fn check_url(&self, url: &str) -> bool;
     async fn get_status_code(&self, url: &str) -> u16;
+
+    // Clients that only know the status code report that and the time taken
+    async fn probe(&self, url: &str) -> Probe {
+        let started = Instant::now();
+        let status = self.get_status_code(url).await;
+        Probe {
+            status,
+            latency: started.elapsed(),
+            failure: (status == 0).then_some(FailureKind::Request),
+            ..Default::default()
+        }
+    }
 }
 
 // Implementation for reqwest::Client
@@ -19,39 +119,104 @@ impl HttpClient for reqwest::Client {
     }
 
     async fn get_status_code(&self, url: &str) -> u16 {
-        match self.get(url).send().await {
-            Ok(res) => res.status().as_u16(),
-            Err(_) => 0,
+        self.probe(url).await.status
+    }
+
+    // Follows redirects by hand so every hop can be recorded
+    async fn probe(&self, url: &str) -> Probe {
+        let started = Instant::now();
+        let mut probe = Probe::default();
+        let mut current = url.to_string();
+
+        loop {
+            let response = match self.get(&current).send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    probe.failure = Some(FailureKind::from(&e));
+                    break;
+                }
+            };
+            if let Some(expiry) = certificate_expiry(&response) {
+                probe.tls_expiry = Some(expiry);
+            }
+
+            match redirect_target(&response) {
+                Some(_) if probe.redirects.len() == MAX_REDIRECTS => {
+                    probe.failure = Some(FailureKind::TooManyRedirects);
+                    break;
+                }
+                Some(next) => {
+                    probe.redirects.push(next.clone());
+                    current = next;
+                }
+                None => {
+                    probe.status = response.status().as_u16();
+                    break;
+                }
+            }
         }
+
+        probe.latency = started.elapsed();
+        probe
     }
 }
 
+fn redirect_target(response: &Response) -> Option<String> {
+    if !response.status().is_redirection() {
+        return None;
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    response.url().join(location).ok().map(String::from)
+}
+
+fn certificate_expiry(response: &Response) -> Option<DateTime<Utc>> {
+    let der = response.extensions().get::<TlsInfo>()?.peer_certificate()?;
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+    DateTime::from_timestamp(certificate.validity().not_after.timestamp(), 0)
+}
+
 // Function to create a client with preset timeout
 pub fn cust_client(timeout: u64) -> Arc<Client> {
     Arc::new(
         Client::builder()
-            .timeout(std::time::Duration::from_secs(timeout))
+            .timeout(Duration::from_secs(timeout))
+            .tls_info(true)
             .build()
             .expect("Failed to build HTTP client"),
     )
 }
 
-// Function to update HTTP status of each website
-pub async fn get_status(client: &Client, url: &str) -> u16 {
-    let status = client.get_status_code(url).await;
+/// A client that leaves redirects to `probe`, so `/check` can report every
+/// hop. Monitoring and deliveries use `cust_client`, which follows them.
+pub fn tracing_client(timeout: u64) -> Client {
+    Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .redirect(Policy::none())
+        .tls_info(true)
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+// Function to probe a website, retrying once if no response was received
+pub async fn probe_site(client: &Client, url: &str) -> Probe {
+    let probe = client.probe(url).await;
 
     // If status is 0, retry once
-    if status == 0 {
+    if probe.status == 0 {
         log::info!("Retrying {url} because status is 0");
-        client.get_status_code(url).await
+        client.probe(url).await
     } else {
-        status
+        probe
     }
 }
 
+// Function to update HTTP status of each website
+pub async fn get_status(client: &Client, url: &str) -> u16 {
+    probe_site(client, url).await.status
+}
+
 pub fn find_changed_websites(original_webs: &[Website], new_statuses: &[u16]) -> Vec<Website> {
-    let datetime: DateTime<Utc> = SystemTime::now().into();
-    let timestamp = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
+    let timestamp = Utc::now();
 
     original_webs
         .iter()
@@ -59,8 +224,9 @@ pub fn find_changed_websites(original_webs: &[Website], new_statuses: &[u16]) ->
         .filter_map(|(web, &new_status)| {
             if web.status != new_status as i32 {
                 let mut updated_web = web.clone();
+                updated_web.previous_status = Some(web.status);
                 updated_web.status = new_status as i32;
-                updated_web.last_updated = timestamp.clone();
+                updated_web.last_updated = timestamp;
                 Some(updated_web)
             } else {
                 None
@@ -168,49 +334,157 @@ mod tests {
         assert_eq!(status, 0);
     }
 
+    // Serve canned responses on a local port: each path maps to a status
+    // line and an optional Location header.
+    async fn serve(
+        routes: &'static [(&'static str, &'static str, Option<&'static str>)],
+    ) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let (_, status, location) = routes
+                    .iter()
+                    .find(|(route, _, _)| *route == path)
+                    .copied()
+                    .unwrap_or(("", "404 Not Found", None));
+                let location = location
+                    .map(|l| format!("Location: {l}\r\n"))
+                    .unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 {status}\r\n{location}Content-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_probe_follows_redirects() {
+        let base = serve(&[
+            ("/", "301 Moved Permanently", Some("/a")),
+            ("/a", "302 Found", Some("/b")),
+            ("/b", "200 OK", None),
+        ])
+        .await;
+        let client = tracing_client(5);
+        let probe = client.probe(&format!("{base}/")).await;
+
+        assert_eq!(probe.status, 200);
+        assert_eq!(probe.failure, None);
+        assert_eq!(
+            probe.redirects,
+            vec![format!("{base}/a"), format!("{base}/b")]
+        );
+        assert_eq!(probe.tls_expiry, None);
+
+        // The shared client follows redirects itself and sees the final status
+        let probe = cust_client(5).probe(&format!("{base}/")).await;
+        assert_eq!(probe.status, 200);
+        assert!(probe.redirects.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_probe_redirect_loop() {
+        let base = serve(&[("/", "302 Found", Some("/"))]).await;
+        let client = tracing_client(5);
+        let probe = client.probe(&format!("{base}/")).await;
+
+        assert_eq!(probe.status, 0);
+        assert_eq!(probe.failure, Some(FailureKind::TooManyRedirects));
+        assert_eq!(probe.redirects.len(), MAX_REDIRECTS);
+
+        let probe = cust_client(5).probe(&format!("{base}/")).await;
+        assert_eq!(probe.failure, Some(FailureKind::TooManyRedirects));
+    }
+
+    #[tokio::test]
+    async fn test_probe_connection_refused() {
+        let client = cust_client(5);
+        let probe = client.probe("http://127.0.0.1:1").await;
+
+        assert_eq!(probe.status, 0);
+        assert_eq!(probe.failure, Some(FailureKind::Connect));
+    }
+
+    #[tokio::test]
+    async fn test_probe_invalid_url() {
+        let client = cust_client(5);
+        let probe = client.probe("not-a-valid-url").await;
+
+        assert_eq!(probe.status, 0);
+        assert_eq!(probe.failure, Some(FailureKind::InvalidUrl));
+    }
+
+    #[tokio::test]
+    async fn test_probe_default_from_status_code() {
+        let client = MockHttpClient {
+            should_succeed: Arc::new(AtomicBool::new(false)),
+            status_code: Arc::new(AtomicBool::new(false)),
+        };
+        let probe = client.probe("https://example.com").await;
+
+        assert_eq!(probe.status, 0);
+        assert_eq!(probe.failure, Some(FailureKind::Request));
+        assert!(probe.redirects.is_empty());
+    }
+
     use super::find_changed_websites;
     use crate::mongo::Website;
+    use chrono::TimeZone;
     use mongodb::bson::oid::ObjectId;
 
     #[test]
     fn test_find_changed_websites_multiple_changes() {
-        let random_date = "2024-01-01 00:00:00".to_string();
+        let random_date = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
         // Original websites
         let original_websites = vec![
             Website {
                 id: Some(ObjectId::new()),
-                last_updated: random_date.clone(),
-                telegram_id: "1234567890".to_string(),
+                last_updated: random_date,
+                chat_id: 1234567890,
                 status: 200,
                 url: "https://example1.com".to_string(),
+                ..Default::default()
             },
             Website {
                 id: Some(ObjectId::new()),
-                last_updated: random_date.clone(),
-                telegram_id: "1234567890".to_string(),
+                last_updated: random_date,
+                chat_id: 1234567890,
                 status: 404,
                 url: "https://example2.com".to_string(),
+                ..Default::default()
             },
             Website {
                 id: Some(ObjectId::new()),
-                last_updated: random_date.clone(),
-                telegram_id: "1234567890".to_string(),
+                last_updated: random_date,
+                chat_id: 1234567890,
                 status: 500,
                 url: "https://example3.com".to_string(),
+                ..Default::default()
             },
             Website {
                 id: Some(ObjectId::new()),
-                last_updated: random_date.clone(),
-                telegram_id: "1234567890".to_string(),
+                last_updated: random_date,
+                chat_id: 1234567890,
                 status: 0,
                 url: "https://example4.com".to_string(),
+                ..Default::default()
             },
             Website {
                 id: Some(ObjectId::new()),
-                last_updated: random_date.clone(),
-                telegram_id: "1234567890".to_string(),
+                last_updated: random_date,
+                chat_id: 1234567890,
                 status: 200,
                 url: "https://example5.com".to_string(),
+                ..Default::default()
             },
         ];
 
@@ -239,19 +513,24 @@ mod tests {
         assert!(!changed_urls.contains(&"https://example5.com".to_string())); // Website 5 unchanged
 
         // Verify that the changed websites have updated timestamps
-        for web in result {
+        for web in &result {
             assert_ne!(web.last_updated, random_date);
         }
+
+        // Verify that the previous status is kept for the transition
+        let previous: Vec<Option<i32>> = result.iter().map(|w| w.previous_status).collect();
+        assert_eq!(previous, vec![Some(404), Some(500), Some(0)]);
     }
 
     #[test]
     fn test_find_changed_websites_no_changes() {
         let original_websites = vec![Website {
             id: Some(ObjectId::new()),
-            last_updated: "2024-01-01 00:00:00".to_string(),
-            telegram_id: "123".to_string(),
+            last_updated: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            chat_id: 123,
             status: 200,
             url: "https://example1.com".to_string(),
+            ..Default::default()
         }];
         let new_statuses = vec![200];
         let changed = find_changed_websites(&original_websites, &new_statuses);
@@ -0,0 +1,1180 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use teloxide::utils::html::escape;
+
+/// A language the bot replies and alerts in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    De,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::En, Language::De];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::De => "de",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::En => "English",
+            Language::De => "Deutsch",
+        }
+    }
+
+    /// Accepts codes and IETF tags such as `de-AT`, as Telegram reports them.
+    pub fn parse(code: &str) -> Option<Self> {
+        let primary = code.split(['-', '_']).next().unwrap_or_default();
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code().eq_ignore_ascii_case(primary))
+    }
+
+    fn catalogue(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::En => EN,
+            Language::De => DE,
+        }
+    }
+}
+
+/// Look up a message, falling back to English when a language lacks it.
+pub fn tr(language: Language, key: &str) -> &'static str {
+    lookup(language, key)
+        .or_else(|| lookup(Language::En, key))
+        .unwrap_or_else(|| panic!("no message for {key}"))
+}
+
+/// Look up a message without falling back.
+pub fn lookup(language: Language, key: &str) -> Option<&'static str> {
+    language
+        .catalogue()
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, message)| *message)
+}
+
+/// Replace each `{name}` in a message with its value. Messages are HTML, so
+/// values are escaped; they are filled in one pass so a value that looks
+/// like a placeholder stays as it is.
+pub fn fill(message: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut output = String::new();
+    let mut rest = message;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            let (_, value) = args.iter().find(|(arg, _)| *arg == name)?;
+            Some((end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                output.push_str(&escape(&value.to_string()));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// A translated message as a `String`, with `name = value` arguments
+/// filled into its `{name}` placeholders.
+macro_rules! t {
+    ($lang:expr, $key:expr) => {
+        $crate::i18n::tr($lang, $key).to_string()
+    };
+    // The arguments are dropped inside the block so they are never held
+    // across an `.await` in the caller
+    ($lang:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let message = $crate::i18n::fill(
+            $crate::i18n::tr($lang, $key),
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        );
+        message
+    }};
+}
+pub(crate) use t;
+
+const EN: &[(&str, &str)] = &[
+    ("on", "on"),
+    ("off", "off"),
+    (
+        "admin_only",
+        "Only chat administrators can change the tracked sites of this group.",
+    ),
+    ("invalid_url", "Invalid URL!"),
+    ("unknown_action", "Unknown action {action}"),
+    ("help.title", "I can understand these commands"),
+    (
+        "about",
+        "<b>ManDown</b>:
+Open Source on <a href='https://github.com/Donnie/ManDown'>GitHub</a>
+Hosted on GCP in us-east-1
+No personally identifiable information is stored or used by this bot.",
+    ),
+    ("language.current", "This chat uses {language}."),
+    ("language.set", "From now on this chat uses {language}."),
+    ("language.unknown", "Unknown language {code}."),
+    ("language.failed_save", "Failed to save the language"),
+    ("ack.none", "No unacknowledged incidents found"),
+    ("ack.done", "Acknowledged {count} incident(s)"),
+    (
+        "ack.error",
+        "An error occurred while acknowledging incidents",
+    ),
+    (
+        "clear.usage",
+        "To clear your entire list of followed domains, please type:",
+    ),
+    ("clear.done", "Successfully cleared {count} site(s)"),
+    ("clear.failed", "Failed to clear user websites: {error}"),
+    ("email.usage", "To also receive alerts by email, type:"),
+    ("email.active", "Alerts are also emailed to {address}"),
+    ("email.pending", "Waiting for the code sent to {address}"),
+    ("email.off", "Email alerts turned off"),
+    ("email.off_failed", "Failed to turn off email alerts"),
+    (
+        "email.verified",
+        "Verified, alerts are now also emailed to {address}",
+    ),
+    ("email.verify_failed", "Failed to verify email address"),
+    (
+        "email.wrong_code",
+        "That code does not match the one I sent",
+    ),
+    (
+        "email.code_expired",
+        "That code is no longer valid. Send /email with your address again for a new one",
+    ),
+    (
+        "email.too_many",
+        "Only {max} verification emails can be sent per hour, please try again later",
+    ),
+    (
+        "email.nothing_pending",
+        "There is no address waiting for verification",
+    ),
+    (
+        "email.not_configured",
+        "Email alerts are not set up on this bot",
+    ),
+    ("email.subject", "Confirm your ManDown alerts"),
+    (
+        "email.body",
+        "Send <b>/email verify {code}</b> to the bot to receive ManDown alerts at this address.",
+    ),
+    (
+        "email.send_failed",
+        "I could not send an email to {address}",
+    ),
+    (
+        "email.code_sent",
+        "I sent a code to {address}. Confirm it within {minutes} minutes with <code>/email verify &lt;code&gt;</code>",
+    ),
+    ("email.failed_save", "Failed to save email address"),
+    ("email.alert_subject", "{site}: status {status}"),
+    (
+        "escalate.usage",
+        "To forward incidents nobody acknowledged to another chat, type:",
+    ),
+    ("escalate.off", "Escalation turned off"),
+    ("escalate.off_failed", "Failed to turn off escalation"),
+    (
+        "escalate.not_allowed",
+        "I can only escalate to a chat I am a member of and that you administer ({target})",
+    ),
+    (
+        "escalate.saved",
+        "Unacknowledged incidents will be escalated to {target} after {after}",
+    ),
+    ("escalate.failed_save", "Failed to save escalation"),
+    ("list.failed", "Failed to get user websites"),
+    ("list.empty", "You aren't tracking any sites yet."),
+    ("list.title", "Here are your tracked domains:"),
+    ("list.status", "Status"),
+    ("list.state", "State"),
+    ("list.checked", "Checked"),
+    ("list.response", "Response"),
+    ("list.up_for", "up {duration}"),
+    ("list.down_for", "down {duration}"),
+    ("list.ago", "{duration} ago"),
+    ("list.no_match", "None of your tracked sites match."),
+    ("list.page", "Page {page} of {pages}"),
+    ("list.usage", "To filter or sort your sites, type:"),
+    (
+        "list.too_long",
+        "A filter can be at most {max} characters long.",
+    ),
+    ("list.unknown_sort", "Unknown sort order {sort}."),
+    ("list.unknown_key", "Unknown filter {key}:"),
+    (
+        "tag.invalid",
+        "Invalid tag {tag}: use up to {max} letters, digits, - or _.",
+    ),
+    ("tag.too_many", "A site can have at most {max} tags."),
+    (
+        "schedule.not_site",
+        "{arg} is neither a site nor a list of days.",
+    ),
+    (
+        "schedule.window_arguments",
+        "Expected days, a time range and a timezone.",
+    ),
+    (
+        "schedule.hours_arguments",
+        "Expected a time range and a timezone.",
+    ),
+    ("schedule.unknown_days", "Unknown days {days}."),
+    ("schedule.invalid_times", "Invalid time range {times}."),
+    ("schedule.unknown_timezone", "Unknown timezone {timezone}."),
+    ("settings.unknown_kind", "Unknown notification kind {kind}."),
+    ("connect.missing_kind", "Expected a channel kind."),
+    ("connect.unknown_kind", "Unknown channel kind {kind}."),
+    (
+        "connect.webhook_arguments",
+        "Expected the {kind} webhook URL.",
+    ),
+    (
+        "connect.ntfy_arguments",
+        "Expected the topic URL and optionally a token.",
+    ),
+    (
+        "connect.gotify_arguments",
+        "Expected the server URL and an application token.",
+    ),
+    (
+        "connect.matrix_arguments",
+        "Expected the homeserver URL, a room ID like !abc:example.org and an access token.",
+    ),
+    (
+        "connect.service_url",
+        "Expected an https URL on {host}, got {url}.",
+    ),
+    ("connect.http_url", "Expected an http(s) URL, got {url}."),
+    (
+        "connect.not_public",
+        "{channel} does not resolve to a public address. Servers on loopback, link-local or private networks are refused.",
+    ),
+    ("channel.down", "{site} is down"),
+    ("channel.recovery", "{site} is back up"),
+    ("channel.changed", "{site} changed status"),
+    ("channel.status", "Status: {status}"),
+    ("channel.status_was", "Status: {status} (was {previous})"),
+    ("channel.was", "Was"),
+    ("channel.failure", "Failure"),
+    (
+        "maintenance.failed_get",
+        "Failed to get maintenance windows",
+    ),
+    ("maintenance.added", "Maintenance window added"),
+    ("maintenance.removed", "Maintenance window removed"),
+    ("maintenance.missing", "There is no maintenance window {n}"),
+    ("maintenance.cleared", "Maintenance windows cleared"),
+    (
+        "maintenance.failed_save",
+        "Failed to save maintenance windows",
+    ),
+    ("maintenance.none", "No maintenance windows yet."),
+    ("maintenance.daily", "daily"),
+    ("maintenance.summary", "summary"),
+    (
+        "maintenance.list",
+        "Alerts are withheld during these maintenance windows:",
+    ),
+    ("webhook.failed_get", "Failed to get webhooks"),
+    ("webhook.log_failed", "Failed to get webhook deliveries"),
+    (
+        "webhook.invalid",
+        "Invalid webhook URL: {url}. It must be an http(s) URL on a public address",
+    ),
+    ("webhook.limit", "A chat can have at most {max} webhooks"),
+    (
+        "webhook.added",
+        "Webhook added. Requests carry the Unix time in the {timestamp} header, and the {header} header holds the HMAC-SHA256 of that time, a dot and the body, keyed with this secret:",
+    ),
+    (
+        "webhook.secret_sent",
+        "Webhook added. I sent you its signing secret in a private chat",
+    ),
+    (
+        "webhook.private_failed",
+        "I could not send you the signing secret privately. Start a chat with me first, then add the webhook again",
+    ),
+    ("webhook.removed", "Webhook removed"),
+    ("webhook.missing", "There is no webhook {n}"),
+    ("webhook.failed_save", "Failed to save webhooks"),
+    ("webhook.none", "No webhooks yet."),
+    (
+        "webhook.list",
+        "Status changes are posted to these webhooks:",
+    ),
+    ("deliveries.none", "No webhook deliveries in the last week."),
+    ("deliveries.title", "Latest webhook deliveries:"),
+    ("deliveries.attempts", "{attempts} attempts"),
+    ("connect.failed_get", "Failed to get channels"),
+    ("connect.connected", "Connected {channel}"),
+    ("connect.disconnected", "Disconnected {channel}"),
+    ("connect.missing", "There is no channel {n}"),
+    ("connect.limit", "A chat can connect at most {max} channels"),
+    ("connect.failed_save", "Failed to save channels"),
+    ("connect.none", "No channels connected yet."),
+    ("connect.list", "Status changes are also posted to:"),
+    ("template.failed_get", "Failed to get templates"),
+    ("template.title", "Alert templates"),
+    ("template.custom", "custom"),
+    ("template.builtin", "built-in"),
+    ("template.unknown", "Unknown template {name}"),
+    ("template.using_builtin", "Using the built-in template:"),
+    ("template.invalid", "Invalid template: {error}"),
+    ("template.failed_save", "Failed to save templates"),
+    (
+        "template.reset",
+        "The {class} template is back to the built-in one",
+    ),
+    (
+        "template.saved",
+        "The {class} template is saved. It now looks like this:",
+    ),
+    ("template.classes", "Classes"),
+    ("template.variables", "Variables"),
+    (
+        "template.if_help",
+        "Wrap text in {{#if failure}}…{{/if}} to show it only when the variable is set.",
+    ),
+    (
+        "pause.usage",
+        "To pause checks for a site, optionally for a while, type:",
+    ),
+    (
+        "pause.until",
+        "Paused {count} site(s) for {hostname}, resuming in {duration}",
+    ),
+    (
+        "pause.done",
+        "Paused {count} site(s) for {hostname} until you /resume them",
+    ),
+    ("pause.error", "An error occurred while pausing {hostname}"),
+    (
+        "pause.invalid_duration",
+        "Invalid duration: use e.g. 30m, 2h or 3d, up to {max}",
+    ),
+    ("resume.none", "No paused sites found for {hostname}"),
+    ("resume.done", "Resumed {count} site(s) for {hostname}"),
+    (
+        "resume.error",
+        "An error occurred while resuming {hostname}",
+    ),
+    ("sites.none", "No sites found for {hostname}"),
+    (
+        "tag.usage",
+        "Tag sites to filter /list and to pause, resume or notify about them together:",
+    ),
+    ("tag.none", "None of your sites has a tag yet."),
+    ("tag.title", "Tags"),
+    ("tag.count", "{tag}: {count} site(s)"),
+    ("tag.set", "Tagged {count} site(s) for {hostname}: {tags}"),
+    (
+        "tag.cleared",
+        "Removed the tags of {count} site(s) for {hostname}",
+    ),
+    ("tag.error", "An error occurred while tagging {hostname}"),
+    ("track.failed", "Failed to track <code>{url}</code>"),
+    (
+        "untrack.done",
+        "Successfully untracked {count} site(s) for {hostname}",
+    ),
+    (
+        "untrack.error",
+        "An error occurred while untracking {hostname}",
+    ),
+    ("settings.failed_get", "Failed to get settings"),
+    ("settings.failed_save", "Failed to save settings"),
+    ("settings.quiet_off", "Quiet hours turned off"),
+    ("settings.quiet_set", "Quiet hours set to {quiet}"),
+    (
+        "settings.notify_set",
+        "Notifications for {kind} turned {state}",
+    ),
+    (
+        "settings.digest_off",
+        "Digest turned off, alerts are sent right away",
+    ),
+    (
+        "settings.digest_set",
+        "Changes are now collected into a {interval} digest",
+    ),
+    (
+        "settings.digest_unknown",
+        "Unknown digest interval: {interval}",
+    ),
+    ("settings.unknown", "Unknown setting"),
+    (
+        "settings.tag_notify_set",
+        "Notifications for {kind} on sites tagged {tag} turned {state}",
+    ),
+    (
+        "settings.tag_notify_reset",
+        "Sites tagged {tag} follow the chat's notification settings again",
+    ),
+    (
+        "settings.tag_notify",
+        "Sites tagged {tag}: down {down}, recovery {recovery}, other changes {change}",
+    ),
+    (
+        "settings.summary",
+        "<b>Settings</b>
+Quiet hours (silent alerts): {quiet}
+Digest instead of instant alerts: {digest}
+Notify when a site goes down: {down}
+Notify when a site recovers: {recovery}
+Notify on other status changes: {change}
+Language: {language}",
+    ),
+    ("digest.hourly", "hourly"),
+    ("digest.daily", "daily"),
+    (
+        "callback.admin_only",
+        "Only chat administrators can do that.",
+    ),
+    ("callback.gone", "This site is no longer tracked."),
+    ("callback.error", "Something went wrong, please try again."),
+    ("callback.rechecked", "Re-checked {url}"),
+    ("callback.muted", "🔕 {name} muted {url} for 1 hour"),
+    ("callback.mute_failed", "Failed to mute {url}"),
+    ("callback.no_incident", "{url} has no open incident"),
+    ("callback.acknowledged", "✅ {name} acknowledged {url}"),
+    ("callback.ack_failed", "Failed to acknowledge {url}"),
+    ("callback.untracked", "🗑 {name} untracked {url}"),
+    ("callback.untrack_failed", "Failed to untrack {url}"),
+    ("button.recheck", "🔄 Re-check now"),
+    ("button.mute", "🔕 Mute 1h"),
+    ("button.ack", "✅ Acknowledge"),
+    ("button.untrack", "🗑 Untrack"),
+    ("button.prev", "« Previous"),
+    ("button.next", "Next »"),
+    ("alert.grouped", "{count} sites changed status"),
+    ("alert.digest", "📋 Digest: {count} status changes"),
+    ("alert.reminder", "⏰ Still unacknowledged, {down}"),
+    (
+        "alert.maintenance_over",
+        "🛠 Maintenance is over but the site is still {down}",
+    ),
+    (
+        "alert.escalated",
+        "🚨 Escalated: no one acknowledged this incident, {down}",
+    ),
+    ("alert.down", "down"),
+    ("alert.down_for", "down for {duration}"),
+    ("changes.was", "Was"),
+    ("changes.now", "Now"),
+    ("probe.failed", "Status: failed ({failure})"),
+    ("failure.invalid_url", "invalid URL"),
+    ("failure.dns", "DNS lookup failed"),
+    ("failure.connect", "connection failed"),
+    ("failure.tls", "TLS handshake failed"),
+    ("failure.timeout", "timed out"),
+    ("failure.too_many_redirects", "too many redirects"),
+    ("failure.request", "request failed"),
+    ("probe.status", "Status: {status}"),
+    ("probe.latency", "Latency: {ms} ms"),
+    ("probe.redirects", "Redirects: {url} → {chain}"),
+    ("probe.tls", "TLS expires: {date} ({days} days)"),
+    (
+        "template.unreachable",
+        "Site: {{site}}\n\nHoppla! We faced an error trying to reach the site! 🤒",
+    ),
+    (
+        "template.up",
+        "Site: {{site}}\n\nJoohoo! It's live and kicking! 🙂\n\nStatus: <a href='https://httpstatuses.com/{{status}}'>{{status}}</a>",
+    ),
+    (
+        "template.4xx",
+        "Site: {{site}}\n\nErm! Did I do something wrong? 🤔\n\nStatus: <a href='https://httpstatuses.com/{{status}}'>{{status}}</a>",
+    ),
+    (
+        "template.5xx",
+        "Site: {{site}}\n\nSchade! It's down or inaccessible to me! 😟\n\nStatus: <a href='https://httpstatuses.com/{{status}}'>{{status}}</a>",
+    ),
+    ("template.other", "Site: {{site}}\n\nSomething is fishy! 🐟"),
+];
+
+// Command descriptions are only listed here for languages other than
+// English; English uses the ones on the `Command` enum
+const DE: &[(&str, &str)] = &[
+    ("on", "an"),
+    ("off", "aus"),
+    (
+        "admin_only",
+        "Nur Administratoren dieses Chats können die überwachten Seiten ändern.",
+    ),
+    ("invalid_url", "Ungültige URL!"),
+    ("unknown_action", "Unbekannte Aktion {action}"),
+    ("help.title", "Diese Befehle verstehe ich"),
+    (
+        "about",
+        "<b>ManDown</b>:
+Open Source auf <a href='https://github.com/Donnie/ManDown'>GitHub</a>
+Gehostet auf GCP in us-east-1
+Dieser Bot speichert und verwendet keine personenbezogenen Daten.",
+    ),
+    ("language.current", "Dieser Chat verwendet {language}."),
+    ("language.set", "Dieser Chat verwendet ab jetzt {language}."),
+    ("language.unknown", "Unbekannte Sprache {code}."),
+    (
+        "language.failed_save",
+        "Die Sprache konnte nicht gespeichert werden",
+    ),
+    ("ack.none", "Keine unbestätigten Vorfälle gefunden"),
+    ("ack.done", "{count} Vorfall/Vorfälle bestätigt"),
+    (
+        "ack.error",
+        "Beim Bestätigen der Vorfälle ist ein Fehler aufgetreten",
+    ),
+    (
+        "clear.usage",
+        "Um die gesamte Liste der überwachten Domains zu leeren, sende:",
+    ),
+    ("clear.done", "{count} Seite(n) entfernt"),
+    (
+        "clear.failed",
+        "Die Seiten konnten nicht entfernt werden: {error}",
+    ),
+    (
+        "email.usage",
+        "Um Alarme zusätzlich per E-Mail zu erhalten, sende:",
+    ),
+    (
+        "email.active",
+        "Alarme gehen zusätzlich per E-Mail an {address}",
+    ),
+    (
+        "email.pending",
+        "Warte auf den Code, der an {address} geschickt wurde",
+    ),
+    ("email.off", "E-Mail-Alarme ausgeschaltet"),
+    (
+        "email.off_failed",
+        "E-Mail-Alarme konnten nicht ausgeschaltet werden",
+    ),
+    (
+        "email.verified",
+        "Bestätigt, Alarme gehen jetzt auch an {address}",
+    ),
+    (
+        "email.verify_failed",
+        "Die E-Mail-Adresse konnte nicht bestätigt werden",
+    ),
+    (
+        "email.wrong_code",
+        "Dieser Code stimmt nicht mit dem gesendeten überein",
+    ),
+    (
+        "email.code_expired",
+        "Dieser Code ist nicht mehr gültig. Sende /email mit deiner Adresse erneut für einen neuen",
+    ),
+    (
+        "email.too_many",
+        "Pro Stunde können nur {max} Bestätigungs-E-Mails gesendet werden, bitte versuche es später erneut",
+    ),
+    (
+        "email.nothing_pending",
+        "Es wartet keine Adresse auf Bestätigung",
+    ),
+    (
+        "email.not_configured",
+        "E-Mail-Alarme sind für diesen Bot nicht eingerichtet",
+    ),
+    ("email.subject", "Bestätige deine ManDown-Alarme"),
+    (
+        "email.body",
+        "Sende <b>/email verify {code}</b> an den Bot, um ManDown-Alarme an diese Adresse zu erhalten.",
+    ),
+    (
+        "email.send_failed",
+        "Ich konnte keine E-Mail an {address} senden",
+    ),
+    (
+        "email.code_sent",
+        "Ich habe einen Code an {address} geschickt. Bestätige ihn innerhalb von {minutes} Minuten mit <code>/email verify &lt;code&gt;</code>",
+    ),
+    (
+        "email.failed_save",
+        "Die E-Mail-Adresse konnte nicht gespeichert werden",
+    ),
+    ("email.alert_subject", "{site}: Status {status}"),
+    (
+        "escalate.usage",
+        "Um Vorfälle, die niemand bestätigt, an einen anderen Chat weiterzuleiten, sende:",
+    ),
+    ("escalate.off", "Eskalation ausgeschaltet"),
+    (
+        "escalate.off_failed",
+        "Die Eskalation konnte nicht ausgeschaltet werden",
+    ),
+    (
+        "escalate.not_allowed",
+        "Ich kann nur an Chats eskalieren, in denen ich Mitglied bin und die du verwaltest ({target})",
+    ),
+    (
+        "escalate.saved",
+        "Unbestätigte Vorfälle werden nach {after} an {target} eskaliert",
+    ),
+    (
+        "escalate.failed_save",
+        "Die Eskalation konnte nicht gespeichert werden",
+    ),
+    ("list.failed", "Die Seiten konnten nicht geladen werden"),
+    ("list.empty", "Du überwachst noch keine Seiten."),
+    ("list.title", "Das sind deine überwachten Domains:"),
+    ("list.status", "Status"),
+    ("list.state", "Zustand"),
+    ("list.checked", "Geprüft"),
+    ("list.response", "Antwort"),
+    ("list.up_for", "online {duration}"),
+    ("list.down_for", "offline {duration}"),
+    ("list.ago", "vor {duration}"),
+    ("list.no_match", "Keine deiner überwachten Seiten passt."),
+    ("list.page", "Seite {page} von {pages}"),
+    (
+        "list.usage",
+        "Um deine Seiten zu filtern oder zu sortieren, sende:",
+    ),
+    (
+        "list.too_long",
+        "Ein Filter darf höchstens {max} Zeichen lang sein.",
+    ),
+    ("list.unknown_sort", "Unbekannte Sortierung {sort}."),
+    ("list.unknown_key", "Unbekannter Filter {key}:"),
+    (
+        "tag.invalid",
+        "Ungültiger Tag {tag}: erlaubt sind bis zu {max} Buchstaben, Ziffern, - oder _.",
+    ),
+    (
+        "tag.too_many",
+        "Eine Seite kann höchstens {max} Tags haben.",
+    ),
+    (
+        "schedule.not_site",
+        "{arg} ist weder eine Seite noch eine Liste von Tagen.",
+    ),
+    (
+        "schedule.window_arguments",
+        "Erwartet werden Tage, ein Zeitraum und eine Zeitzone.",
+    ),
+    (
+        "schedule.hours_arguments",
+        "Erwartet werden ein Zeitraum und eine Zeitzone.",
+    ),
+    ("schedule.unknown_days", "Unbekannte Tage {days}."),
+    ("schedule.invalid_times", "Ungültiger Zeitraum {times}."),
+    (
+        "schedule.unknown_timezone",
+        "Unbekannte Zeitzone {timezone}.",
+    ),
+    (
+        "settings.unknown_kind",
+        "Unbekannte Art von Benachrichtigung {kind}.",
+    ),
+    ("connect.missing_kind", "Erwartet wird die Art des Kanals."),
+    ("connect.unknown_kind", "Unbekannte Art von Kanal {kind}."),
+    (
+        "connect.webhook_arguments",
+        "Erwartet wird die {kind}-Webhook-URL.",
+    ),
+    (
+        "connect.ntfy_arguments",
+        "Erwartet werden die Topic-URL und optional ein Token.",
+    ),
+    (
+        "connect.gotify_arguments",
+        "Erwartet werden die Server-URL und ein Anwendungs-Token.",
+    ),
+    (
+        "connect.matrix_arguments",
+        "Erwartet werden die Homeserver-URL, eine Raum-ID wie !abc:example.org und ein Zugriffstoken.",
+    ),
+    (
+        "connect.service_url",
+        "Erwartet wird eine https-URL auf {host}, nicht {url}.",
+    ),
+    (
+        "connect.http_url",
+        "Erwartet wird eine http(s)-URL, nicht {url}.",
+    ),
+    (
+        "connect.not_public",
+        "{channel} hat keine öffentliche Adresse. Server im Loopback-, Link-Local- oder privaten Netz werden abgelehnt.",
+    ),
+    ("channel.down", "{site} ist nicht erreichbar"),
+    ("channel.recovery", "{site} ist wieder erreichbar"),
+    ("channel.changed", "{site} hat den Status geändert"),
+    ("channel.status", "Status: {status}"),
+    ("channel.status_was", "Status: {status} (vorher {previous})"),
+    ("channel.was", "Vorher"),
+    ("channel.failure", "Fehler"),
+    (
+        "maintenance.failed_get",
+        "Die Wartungsfenster konnten nicht geladen werden",
+    ),
+    ("maintenance.added", "Wartungsfenster hinzugefügt"),
+    ("maintenance.removed", "Wartungsfenster entfernt"),
+    ("maintenance.missing", "Es gibt kein Wartungsfenster {n}"),
+    ("maintenance.cleared", "Alle Wartungsfenster entfernt"),
+    (
+        "maintenance.failed_save",
+        "Die Wartungsfenster konnten nicht gespeichert werden",
+    ),
+    ("maintenance.none", "Noch keine Wartungsfenster."),
+    ("maintenance.daily", "täglich"),
+    ("maintenance.summary", "Zusammenfassung"),
+    (
+        "maintenance.list",
+        "Während dieser Wartungsfenster werden Alarme zurückgehalten:",
+    ),
+    (
+        "webhook.failed_get",
+        "Die Webhooks konnten nicht geladen werden",
+    ),
+    (
+        "webhook.log_failed",
+        "Die Webhook-Zustellungen konnten nicht geladen werden",
+    ),
+    (
+        "webhook.invalid",
+        "Ungültige Webhook-URL: {url}. Sie muss eine http(s)-URL mit öffentlicher Adresse sein",
+    ),
+    (
+        "webhook.limit",
+        "Ein Chat kann höchstens {max} Webhooks haben",
+    ),
+    (
+        "webhook.added",
+        "Webhook hinzugefügt. Anfragen enthalten die Unix-Zeit im Header {timestamp}, und der Header {header} enthält den HMAC-SHA256 aus dieser Zeit, einem Punkt und dem Inhalt, mit diesem Schlüssel:",
+    ),
+    (
+        "webhook.secret_sent",
+        "Webhook hinzugefügt. Den Signaturschlüssel habe ich dir privat geschickt",
+    ),
+    (
+        "webhook.private_failed",
+        "Ich konnte dir den Signaturschlüssel nicht privat schicken. Starte zuerst einen Chat mit mir und füge den Webhook dann erneut hinzu",
+    ),
+    ("webhook.removed", "Webhook entfernt"),
+    ("webhook.missing", "Es gibt keinen Webhook {n}"),
+    (
+        "webhook.failed_save",
+        "Die Webhooks konnten nicht gespeichert werden",
+    ),
+    ("webhook.none", "Noch keine Webhooks."),
+    (
+        "webhook.list",
+        "Statusänderungen werden an diese Webhooks gesendet:",
+    ),
+    (
+        "deliveries.none",
+        "Keine Webhook-Zustellungen in der letzten Woche.",
+    ),
+    ("deliveries.title", "Letzte Webhook-Zustellungen:"),
+    ("deliveries.attempts", "{attempts} Versuche"),
+    (
+        "connect.failed_get",
+        "Die Kanäle konnten nicht geladen werden",
+    ),
+    ("connect.connected", "{channel} verbunden"),
+    ("connect.disconnected", "{channel} getrennt"),
+    ("connect.missing", "Es gibt keinen Kanal {n}"),
+    (
+        "connect.limit",
+        "Ein Chat kann höchstens {max} Kanäle verbinden",
+    ),
+    (
+        "connect.failed_save",
+        "Die Kanäle konnten nicht gespeichert werden",
+    ),
+    ("connect.none", "Noch keine Kanäle verbunden."),
+    ("connect.list", "Statusänderungen gehen außerdem an:"),
+    (
+        "template.failed_get",
+        "Die Vorlagen konnten nicht geladen werden",
+    ),
+    ("template.title", "Alarmvorlagen"),
+    ("template.custom", "eigene"),
+    ("template.builtin", "eingebaut"),
+    ("template.unknown", "Unbekannte Vorlage {name}"),
+    (
+        "template.using_builtin",
+        "Verwendet die eingebaute Vorlage:",
+    ),
+    ("template.invalid", "Ungültige Vorlage: {error}"),
+    (
+        "template.failed_save",
+        "Die Vorlagen konnten nicht gespeichert werden",
+    ),
+    (
+        "template.reset",
+        "Die Vorlage {class} ist wieder die eingebaute",
+    ),
+    (
+        "template.saved",
+        "Die Vorlage {class} ist gespeichert. So sieht sie jetzt aus:",
+    ),
+    ("template.classes", "Klassen"),
+    ("template.variables", "Variablen"),
+    (
+        "template.if_help",
+        "Text in {{#if failure}}…{{/if}} erscheint nur, wenn die Variable einen Wert hat.",
+    ),
+    (
+        "pause.usage",
+        "Um die Prüfung einer Seite, optional für eine Weile, zu pausieren, sende:",
+    ),
+    (
+        "pause.until",
+        "{count} Seite(n) für {hostname} pausiert, weiter in {duration}",
+    ),
+    (
+        "pause.done",
+        "{count} Seite(n) für {hostname} pausiert, bis du sie mit /resume fortsetzt",
+    ),
+    (
+        "pause.error",
+        "Beim Pausieren von {hostname} ist ein Fehler aufgetreten",
+    ),
+    (
+        "pause.invalid_duration",
+        "Ungültige Dauer: nutze z. B. 30m, 2h oder 3d, höchstens {max}",
+    ),
+    (
+        "resume.none",
+        "Keine pausierten Seiten für {hostname} gefunden",
+    ),
+    ("resume.done", "{count} Seite(n) für {hostname} fortgesetzt"),
+    (
+        "resume.error",
+        "Beim Fortsetzen von {hostname} ist ein Fehler aufgetreten",
+    ),
+    ("sites.none", "Keine Seiten für {hostname} gefunden"),
+    (
+        "tag.usage",
+        "Markiere Seiten, um /list zu filtern und sie gemeinsam zu pausieren, fortzusetzen oder zu melden:",
+    ),
+    ("tag.none", "Noch hat keine deiner Seiten einen Tag."),
+    ("tag.title", "Tags"),
+    ("tag.count", "{tag}: {count} Seite(n)"),
+    (
+        "tag.set",
+        "{count} Seite(n) für {hostname} markiert: {tags}",
+    ),
+    (
+        "tag.cleared",
+        "Tags von {count} Seite(n) für {hostname} entfernt",
+    ),
+    (
+        "tag.error",
+        "Beim Markieren von {hostname} ist ein Fehler aufgetreten",
+    ),
+    (
+        "track.failed",
+        "<code>{url}</code> konnte nicht überwacht werden",
+    ),
+    (
+        "untrack.done",
+        "{count} Seite(n) für {hostname} werden nicht mehr überwacht",
+    ),
+    (
+        "untrack.error",
+        "Beim Entfernen von {hostname} ist ein Fehler aufgetreten",
+    ),
+    (
+        "settings.failed_get",
+        "Die Einstellungen konnten nicht geladen werden",
+    ),
+    (
+        "settings.failed_save",
+        "Die Einstellungen konnten nicht gespeichert werden",
+    ),
+    ("settings.quiet_off", "Ruhezeiten ausgeschaltet"),
+    ("settings.quiet_set", "Ruhezeiten auf {quiet} gesetzt"),
+    (
+        "settings.notify_set",
+        "Benachrichtigungen für {kind}: {state}",
+    ),
+    (
+        "settings.digest_off",
+        "Zusammenfassung ausgeschaltet, Alarme kommen wieder sofort",
+    ),
+    (
+        "settings.digest_set",
+        "Änderungen werden jetzt gesammelt und {interval} zusammengefasst",
+    ),
+    (
+        "settings.digest_unknown",
+        "Unbekanntes Intervall: {interval}",
+    ),
+    ("settings.unknown", "Unbekannte Einstellung"),
+    (
+        "settings.tag_notify_set",
+        "Benachrichtigungen für {kind} bei Seiten mit Tag {tag}: {state}",
+    ),
+    (
+        "settings.tag_notify_reset",
+        "Seiten mit Tag {tag} folgen wieder den Einstellungen des Chats",
+    ),
+    (
+        "settings.tag_notify",
+        "Seiten mit Tag {tag}: Ausfall {down}, Erholung {recovery}, andere Änderungen {change}",
+    ),
+    (
+        "settings.summary",
+        "<b>Einstellungen</b>
+Ruhezeiten (lautlose Alarme): {quiet}
+Zusammenfassung statt sofortiger Alarme: {digest}
+Benachrichtigen, wenn eine Seite ausfällt: {down}
+Benachrichtigen, wenn eine Seite wieder läuft: {recovery}
+Bei anderen Statusänderungen benachrichtigen: {change}
+Sprache: {language}",
+    ),
+    ("digest.hourly", "stündlich"),
+    ("digest.daily", "täglich"),
+    (
+        "callback.admin_only",
+        "Das können nur Administratoren des Chats.",
+    ),
+    ("callback.gone", "Diese Seite wird nicht mehr überwacht."),
+    (
+        "callback.error",
+        "Etwas ist schiefgelaufen, bitte versuche es noch einmal.",
+    ),
+    ("callback.rechecked", "{url} erneut geprüft"),
+    (
+        "callback.muted",
+        "🔕 {name} hat {url} für 1 Stunde stummgeschaltet",
+    ),
+    (
+        "callback.mute_failed",
+        "{url} konnte nicht stummgeschaltet werden",
+    ),
+    ("callback.no_incident", "{url} hat keinen offenen Vorfall"),
+    ("callback.acknowledged", "✅ {name} hat {url} bestätigt"),
+    ("callback.ack_failed", "{url} konnte nicht bestätigt werden"),
+    ("callback.untracked", "🗑 {name} überwacht {url} nicht mehr"),
+    (
+        "callback.untrack_failed",
+        "{url} konnte nicht entfernt werden",
+    ),
+    ("button.recheck", "🔄 Jetzt prüfen"),
+    ("button.mute", "🔕 1 Std. stumm"),
+    ("button.ack", "✅ Bestätigen"),
+    ("button.untrack", "🗑 Entfernen"),
+    ("button.prev", "« Zurück"),
+    ("button.next", "Weiter »"),
+    (
+        "alert.grouped",
+        "{count} Seiten haben ihren Status geändert",
+    ),
+    (
+        "alert.digest",
+        "📋 Zusammenfassung: {count} Statusänderungen",
+    ),
+    ("alert.reminder", "⏰ Immer noch unbestätigt, {down}"),
+    (
+        "alert.maintenance_over",
+        "🛠 Die Wartung ist vorbei, aber die Seite ist immer noch {down}",
+    ),
+    (
+        "alert.escalated",
+        "🚨 Eskaliert: niemand hat diesen Vorfall bestätigt, {down}",
+    ),
+    ("alert.down", "ausgefallen"),
+    ("alert.down_for", "seit {duration} ausgefallen"),
+    ("changes.was", "Vorher"),
+    ("changes.now", "Jetzt"),
+    ("probe.failed", "Status: fehlgeschlagen ({failure})"),
+    ("failure.invalid_url", "ungültige URL"),
+    ("failure.dns", "DNS-Auflösung fehlgeschlagen"),
+    ("failure.connect", "Verbindung fehlgeschlagen"),
+    ("failure.tls", "TLS-Handshake fehlgeschlagen"),
+    ("failure.timeout", "Zeitüberschreitung"),
+    ("failure.too_many_redirects", "zu viele Weiterleitungen"),
+    ("failure.request", "Anfrage fehlgeschlagen"),
+    ("probe.status", "Status: {status}"),
+    ("probe.latency", "Latenz: {ms} ms"),
+    ("probe.redirects", "Weiterleitungen: {url} → {chain}"),
+    ("probe.tls", "TLS läuft ab: {date} (in {days} Tagen)"),
+    (
+        "template.unreachable",
+        "Seite: {{site}}\n\nHoppla! Ich komme nicht an die Seite heran! 🤒",
+    ),
+    (
+        "template.up",
+        "Seite: {{site}}\n\nJuhu! Sie läuft und läuft! 🙂\n\nStatus: <a href='https://httpstatuses.com/{{status}}'>{{status}}</a>",
+    ),
+    (
+        "template.4xx",
+        "Seite: {{site}}\n\nNanu! Habe ich etwas falsch gemacht? 🤔\n\nStatus: <a href='https://httpstatuses.com/{{status}}'>{{status}}</a>",
+    ),
+    (
+        "template.5xx",
+        "Seite: {{site}}\n\nSchade! Sie ist ausgefallen oder für mich nicht erreichbar! 😟\n\nStatus: <a href='https://httpstatuses.com/{{status}}'>{{status}}</a>",
+    ),
+    (
+        "template.other",
+        "Seite: {{site}}\n\nHier ist etwas faul! 🐟",
+    ),
+    ("command.about", "Über ManDown"),
+    (
+        "command.ack",
+        "Offene Vorfälle bestätigen, für eine Seite oder alle",
+    ),
+    (
+        "command.check",
+        "Eine Seite einmal prüfen, ohne sie zu überwachen",
+    ),
+    (
+        "command.clear",
+        "Die Liste der überwachten Domains dieses Chats leeren",
+    ),
+    (
+        "command.connect",
+        "Statusänderungen auch an Slack, Discord, Matrix, ntfy oder Gotify senden",
+    ),
+    (
+        "command.email",
+        "Alarme zusätzlich per E-Mail an eine bestätigte Adresse erhalten",
+    ),
+    (
+        "command.escalate",
+        "Unbestätigte Vorfälle an einen anderen Chat weiterleiten",
+    ),
+    ("command.help", "Zeigt diesen Text an"),
+    (
+        "command.language",
+        "Sprache der Antworten und Alarme wählen",
+    ),
+    (
+        "command.list",
+        "Zeigt die überwachten Domains an, gefiltert mit down, up, paused oder tag:&lt;name&gt;",
+    ),
+    (
+        "command.maintenance",
+        "Wartungsfenster, in denen Alarme zurückgehalten werden",
+    ),
+    (
+        "command.pause",
+        "Prüfungen einer Seite pausieren, optional für eine Weile (z. B. 2h)",
+    ),
+    (
+        "command.resume",
+        "Prüfungen einer pausierten Seite fortsetzen",
+    ),
+    (
+        "command.settings",
+        "Ruhezeiten und welche Statusänderungen gemeldet werden",
+    ),
+    ("command.start", "Ich bin hier, um zu helfen!"),
+    (
+        "command.tag",
+        "Seiten mit Tags versehen, um sie gemeinsam zu verwalten",
+    ),
+    ("command.template", "Den Wortlaut der Alarme ändern"),
+    ("command.track", "Eine Seite zur Überwachung hinzufügen"),
+    ("command.untrack", "Eine Seite nicht mehr überwachen"),
+    (
+        "command.webhook",
+        "Statusänderungen als signiertes JSON an eigene URLs senden",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn placeholders(message: &str) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        let mut rest = message;
+        while let Some(start) = rest.find('{') {
+            rest = &rest[start + 1..];
+            if let Some(end) = rest.find('}') {
+                let name = &rest[..end];
+                if name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn test_catalogues_match() {
+        for language in Language::ALL {
+            let keys: BTreeSet<&str> = language.catalogue().iter().map(|(key, _)| *key).collect();
+            assert_eq!(
+                keys.len(),
+                language.catalogue().len(),
+                "duplicate keys in {language:?}"
+            );
+        }
+        for (key, english) in EN {
+            let german = lookup(Language::De, key).unwrap_or_else(|| panic!("{key} missing in de"));
+            assert_eq!(placeholders(english), placeholders(german), "{key}");
+        }
+        for (key, _) in DE {
+            assert!(
+                key.starts_with("command.") || lookup(Language::En, key).is_some(),
+                "{key} missing in en"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Language::parse("de"), Some(Language::De));
+        assert_eq!(Language::parse("de-AT"), Some(Language::De));
+        assert_eq!(Language::parse("EN"), Some(Language::En));
+        assert_eq!(Language::parse("fr"), None);
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(
+            t!(
+                Language::De,
+                "resume.done",
+                count = 2,
+                hostname = "example.com"
+            ),
+            "2 Seite(n) für example.com fortgesetzt"
+        );
+        assert_eq!(t!(Language::En, "off"), "off");
+    }
+
+    #[test]
+    fn test_fill_escapes_values() {
+        assert_eq!(
+            t!(
+                Language::En,
+                "sites.none",
+                hostname = "<b>x</b>&amp;{hostname}"
+            ),
+            t!(Language::En, "sites.none", hostname = "HOST")
+                .replace("HOST", "&lt;b&gt;x&lt;/b&gt;&amp;amp;{hostname}")
+        );
+        assert_eq!(
+            fill("{a} {b} {c}", &[("a", &"{b}"), ("b", &1)]),
+            "{b} 1 {c}"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::mongo::Website;
+
+/// An outage that stays open until the site is back to a 2xx status.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Incident {
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub last_notified: DateTime<Utc>,
+    pub acknowledged: bool,
+    pub escalated: bool,
+}
+
+impl Incident {
+    pub fn open(now: DateTime<Utc>) -> Self {
+        Incident {
+            started_at: now,
+            last_notified: now,
+            acknowledged: false,
+            escalated: false,
+        }
+    }
+
+    pub fn reminder_due(&self, now: DateTime<Utc>, interval: Duration) -> bool {
+        !self.acknowledged && now - self.last_notified >= interval
+    }
+
+    pub fn escalation_due(&self, now: DateTime<Utc>, after: Duration) -> bool {
+        !self.acknowledged && !self.escalated && now - self.started_at >= after
+    }
+}
+
+pub fn is_down(status: i32) -> bool {
+    !(200..=299).contains(&status)
+}
+
+/// What kind of status change an alert is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    /// From a 2xx status to anything else
+    Down,
+    /// Back to a 2xx status
+    Recovery,
+    /// Between two statuses of the same class, e.g. 500 to 503
+    StatusChange,
+}
+
+impl Transition {
+    pub fn between(old: i32, new: i32) -> Self {
+        match (is_down(old), is_down(new)) {
+            (false, true) => Transition::Down,
+            (true, false) => Transition::Recovery,
+            _ => Transition::StatusChange,
+        }
+    }
+}
+
+/// Open or close the incident of a site whose status just changed. A change
+/// between two failing statuses keeps the incident but counts as a fresh
+/// notification.
+pub fn track_incident(website: &mut Website, now: DateTime<Utc>) {
+    if !is_down(website.status) {
+        website.outage = website
+            .incident
+            .take()
+            .map(|incident| now - incident.started_at);
+        return;
+    }
+    match website.incident.as_mut() {
+        Some(incident) => incident.last_notified = now,
+        None => website.incident = Some(Incident::open(now)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn website(status: i32, incident: Option<Incident>) -> Website {
+        Website {
+            url: "https://example.com".to_string(),
+            status,
+            incident,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_opens_when_going_down() {
+        let now = Utc::now();
+        let mut site = website(503, None);
+        track_incident(&mut site, now);
+        assert_eq!(site.incident, Some(Incident::open(now)));
+    }
+
+    #[test]
+    fn test_closes_on_recovery() {
+        let now = Utc::now();
+        let mut site = website(200, Some(Incident::open(now - Duration::minutes(5))));
+        track_incident(&mut site, now);
+        assert_eq!(site.incident, None);
+        assert_eq!(site.outage, Some(Duration::minutes(5)));
+    }
+
+    #[test]
+    fn test_keeps_incident_across_failing_statuses() {
+        let started = Utc::now() - Duration::hours(2);
+        let now = Utc::now();
+        let mut incident = Incident::open(started);
+        incident.acknowledged = true;
+        let mut site = website(0, Some(incident));
+        track_incident(&mut site, now);
+
+        let incident = site.incident.unwrap();
+        assert_eq!(incident.started_at, started);
+        assert_eq!(incident.last_notified, now);
+        assert!(incident.acknowledged);
+    }
+
+    #[test]
+    fn test_transition_between() {
+        assert_eq!(Transition::between(200, 503), Transition::Down);
+        assert_eq!(Transition::between(204, 0), Transition::Down);
+        assert_eq!(Transition::between(404, 200), Transition::Recovery);
+        assert_eq!(Transition::between(500, 503), Transition::StatusChange);
+        assert_eq!(Transition::between(200, 204), Transition::StatusChange);
+    }
+
+    #[test]
+    fn test_reminder_due() {
+        let now = Utc::now();
+        let mut incident = Incident::open(now - Duration::minutes(45));
+        assert!(incident.reminder_due(now, Duration::minutes(30)));
+        assert!(!incident.reminder_due(now, Duration::hours(1)));
+
+        incident.acknowledged = true;
+        assert!(!incident.reminder_due(now, Duration::minutes(30)));
+    }
+
+    #[test]
+    fn test_escalation_due_once() {
+        let now = Utc::now();
+        let mut incident = Incident::open(now - Duration::hours(2));
+        assert!(incident.escalation_due(now, Duration::hours(1)));
+        assert!(!incident.escalation_due(now, Duration::hours(3)));
+
+        incident.escalated = true;
+        assert!(!incident.escalation_due(now, Duration::hours(1)));
+    }
+}
@@ -1,10 +1,32 @@
 pub mod alert;
 pub mod baseline;
+pub mod callback;
+pub mod channel;
 pub mod command;
 pub mod config;
+pub mod digest;
+pub mod discord;
+pub mod duration;
+pub mod email;
 pub mod format;
+pub mod gotify;
 pub mod handler;
+pub mod html;
 pub mod http;
+pub mod i18n;
+pub mod incident;
+pub mod list;
+pub mod maintenance;
+pub mod matrix;
+pub mod migrate;
 pub mod mongo;
+pub mod notify;
+pub mod ntfy;
+pub mod outbox;
 pub mod parse_url;
 pub mod poll;
+pub mod settings;
+pub mod slack;
+pub mod tag;
+pub mod template;
+pub mod webhook;
@@ -0,0 +1,404 @@
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::i18n::{Language, t, tr};
+use crate::incident::is_down;
+use crate::mongo::Website;
+use crate::tag::{TagError, parse_tag};
+
+/// Sites shown on one page of `/list`.
+pub const PAGE_SIZE: usize = 10;
+
+// Keeps the button payload within Telegram's 64-byte callback data limit
+const MAX_FILTER_LEN: usize = 40;
+
+const PREFIX: &str = "list";
+
+/// Sites answering slower than this are shown as degraded.
+pub const SLOW_MS: i64 = 2000;
+
+/// How a site is doing, at a glance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    Up,
+    /// Up, but slow to answer
+    Degraded,
+    Down,
+}
+
+impl Health {
+    pub fn of(website: &Website) -> Self {
+        if is_down(website.status) {
+            Health::Down
+        } else if website.latency_ms.is_some_and(|ms| ms >= SLOW_MS) {
+            Health::Degraded
+        } else {
+            Health::Up
+        }
+    }
+
+    pub fn indicator(self) -> &'static str {
+        match self {
+            Health::Up => "🟢",
+            Health::Degraded => "🟡",
+            Health::Down => "🔴",
+        }
+    }
+}
+
+/// Why the arguments of `/list` were refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListError {
+    TooLong,
+    Tag(TagError),
+    UnknownSort(String),
+    /// A `key:` word other than `tag:` or `sort:`, most likely a typo
+    UnknownKey(String),
+}
+
+impl ListError {
+    pub fn message(&self, language: Language) -> String {
+        match self {
+            ListError::TooLong => t!(language, "list.too_long", max = MAX_FILTER_LEN),
+            ListError::Tag(e) => e.message(language),
+            ListError::UnknownSort(name) => t!(language, "list.unknown_sort", sort = name),
+            ListError::UnknownKey(key) => t!(language, "list.unknown_key", key = key),
+        }
+    }
+}
+
+/// Which sites `/list` shows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Filter {
+    #[default]
+    All,
+    Up,
+    Down,
+    Paused,
+    Tag(String),
+    /// Sites whose URL contains the text
+    Url(String),
+}
+
+impl Filter {
+    fn parse(word: &str) -> Result<Self, ListError> {
+        if word.len() > MAX_FILTER_LEN {
+            return Err(ListError::TooLong);
+        }
+        Ok(match word {
+            "all" => Filter::All,
+            "up" => Filter::Up,
+            "down" => Filter::Down,
+            "paused" => Filter::Paused,
+            _ => match word.split_once(':') {
+                Some(("tag", tag)) => Filter::Tag(parse_tag(tag).map_err(ListError::Tag)?),
+                // A word before a colon is a key, unless it is a URL's scheme
+                Some((key, rest))
+                    if key.chars().all(|c| c.is_ascii_alphabetic()) && !rest.starts_with("//") =>
+                {
+                    return Err(ListError::UnknownKey(key.to_string()));
+                }
+                _ => Filter::Url(word.to_string()),
+            },
+        })
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Filter::All => "all".to_string(),
+            Filter::Up => "up".to_string(),
+            Filter::Down => "down".to_string(),
+            Filter::Paused => "paused".to_string(),
+            Filter::Tag(tag) => format!("tag:{tag}"),
+            Filter::Url(text) => text.clone(),
+        }
+    }
+
+    fn matches(&self, website: &Website, now: DateTime<Utc>) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Up => !is_down(website.status),
+            Filter::Down => is_down(website.status),
+            Filter::Paused => website.is_paused(now),
+            Filter::Tag(tag) => website.tags.contains(tag),
+            Filter::Url(text) => website.url.contains(text.as_str()),
+        }
+    }
+}
+
+/// Order of the sites in `/list`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sort {
+    #[default]
+    Url,
+    /// Down sites first, then by status code
+    Status,
+    /// Most recent status change first
+    Changed,
+}
+
+impl Sort {
+    const ALL: [Sort; 3] = [Sort::Url, Sort::Status, Sort::Changed];
+
+    fn name(self) -> &'static str {
+        match self {
+            Sort::Url => "url",
+            Sort::Status => "status",
+            Sort::Changed => "changed",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.name() == name)
+    }
+
+    fn apply(self, websites: &mut [&Website]) {
+        match self {
+            Sort::Url => websites.sort_by(|a, b| a.url.cmp(&b.url)),
+            Sort::Status => websites.sort_by_key(|w| (!is_down(w.status), w.status)),
+            Sort::Changed => websites.sort_by_key(|w| Reverse(w.last_updated)),
+        }
+    }
+}
+
+/// The arguments of `/list`, also carried by its page buttons.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListQuery {
+    pub filter: Filter,
+    pub sort: Sort,
+    /// Zero-based
+    pub page: usize,
+}
+
+impl ListQuery {
+    /// Parse e.g. `down sort:changed` or `tag:prod`.
+    pub fn parse(args: &str) -> Result<Self, ListError> {
+        let mut query = ListQuery::default();
+        for word in args.split_whitespace() {
+            let word = word.to_lowercase();
+            match word.strip_prefix("sort:") {
+                Some(name) => {
+                    query.sort = Sort::parse(name)
+                        .ok_or_else(|| ListError::UnknownSort(name.to_string()))?;
+                }
+                None => query.filter = Filter::parse(&word)?,
+            }
+        }
+        Ok(query)
+    }
+
+    /// Encoded as `list:<page>:<sort>:<filter>`; the filter goes last as a
+    /// tag filter contains a colon itself.
+    pub fn encode(&self) -> String {
+        format!(
+            "{PREFIX}:{}:{}:{}",
+            self.page,
+            self.sort.name(),
+            self.filter.name()
+        )
+    }
+
+    pub fn decode(data: &str) -> Option<Self> {
+        let mut parts = data.splitn(4, ':');
+        if parts.next()? != PREFIX {
+            return None;
+        }
+        let page = parts.next()?.parse().ok()?;
+        let sort = Sort::parse(parts.next()?)?;
+        let filter = Filter::parse(parts.next()?).ok()?;
+        Some(ListQuery { filter, sort, page })
+    }
+
+    /// The sites on the requested page; a page past the end shows the last
+    /// one, as sites may have been untracked since the buttons were sent.
+    pub fn page<'a>(&self, websites: &'a [Website], now: DateTime<Utc>) -> Page<'a> {
+        let mut matching: Vec<&Website> = websites
+            .iter()
+            .filter(|website| self.filter.matches(website, now))
+            .collect();
+        self.sort.apply(&mut matching);
+
+        let pages = matching.len().div_ceil(PAGE_SIZE).max(1);
+        let number = self.page.min(pages - 1);
+        let sites = matching
+            .into_iter()
+            .skip(number * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .collect();
+        Page {
+            sites,
+            number,
+            pages,
+            tracked: websites.len(),
+        }
+    }
+
+    /// Previous and next buttons, if there is more than one page.
+    pub fn keyboard(&self, page: &Page, language: Language) -> Option<InlineKeyboardMarkup> {
+        if page.pages <= 1 {
+            return None;
+        }
+        let button = |key: &str, number| {
+            let query = ListQuery {
+                page: number,
+                ..self.clone()
+            };
+            InlineKeyboardButton::callback(tr(language, key), query.encode())
+        };
+        let mut row = Vec::new();
+        if page.number > 0 {
+            row.push(button("button.prev", page.number - 1));
+        }
+        if page.number + 1 < page.pages {
+            row.push(button("button.next", page.number + 1));
+        }
+        Some(InlineKeyboardMarkup::new([row]))
+    }
+}
+
+/// One page of `/list`.
+pub struct Page<'a> {
+    pub sites: Vec<&'a Website>,
+    /// Zero-based
+    pub number: usize,
+    pub pages: usize,
+    /// All sites of the chat, before filtering
+    pub tracked: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn site(url: &str, status: i32, changed: DateTime<Utc>) -> Website {
+        Website {
+            url: url.to_string(),
+            status,
+            last_updated: changed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_health() {
+        let mut website = site("https://a.com", 200, Utc::now());
+        assert_eq!(Health::of(&website), Health::Up);
+        website.latency_ms = Some(SLOW_MS);
+        assert_eq!(Health::of(&website), Health::Degraded);
+        website.status = 503;
+        assert_eq!(Health::of(&website), Health::Down);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ListQuery::parse(""), Ok(ListQuery::default()));
+        assert_eq!(
+            ListQuery::parse("DOWN sort:changed"),
+            Ok(ListQuery {
+                filter: Filter::Down,
+                sort: Sort::Changed,
+                page: 0
+            })
+        );
+        assert_eq!(
+            ListQuery::parse("tag:prod").map(|query| query.filter),
+            Ok(Filter::Tag("prod".to_string()))
+        );
+        assert_eq!(
+            ListQuery::parse("example.com").map(|query| query.filter),
+            Ok(Filter::Url("example.com".to_string()))
+        );
+        assert_eq!(
+            ListQuery::parse("https://example.com:8080").map(|query| query.filter),
+            Ok(Filter::Url("https://example.com:8080".to_string()))
+        );
+        assert_eq!(
+            ListQuery::parse("sort:size"),
+            Err(ListError::UnknownSort("size".to_string()))
+        );
+        assert_eq!(
+            ListQuery::parse("sotr:status"),
+            Err(ListError::UnknownKey("sotr".to_string()))
+        );
+        assert_eq!(
+            ListQuery::parse("tag:"),
+            Err(ListError::Tag(TagError::Invalid(String::new())))
+        );
+        assert_eq!(ListQuery::parse(&"x".repeat(41)), Err(ListError::TooLong));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let query = ListQuery {
+            filter: Filter::Tag("a".repeat(20)),
+            sort: Sort::Changed,
+            page: 999,
+        };
+        let data = ListQuery {
+            filter: Filter::Url("a".repeat(40)),
+            ..query.clone()
+        }
+        .encode();
+        assert!(data.len() <= 64, "{data}");
+        let data = query.encode();
+        assert!(data.len() <= 64, "{data}");
+        assert_eq!(ListQuery::decode(&data), Some(query));
+        assert_eq!(ListQuery::decode("recheck:abc"), None);
+        assert_eq!(ListQuery::decode("list:x:url:all"), None);
+    }
+
+    #[test]
+    fn test_pages() {
+        let now = Utc::now();
+        let websites: Vec<Website> = (0..25)
+            .map(|n| site(&format!("https://{n:02}.com"), 200, now))
+            .collect();
+        let query = ListQuery::default();
+
+        let page = query.page(&websites, now);
+        assert_eq!((page.number, page.pages, page.sites.len()), (0, 3, 10));
+        assert!(query.keyboard(&page, Language::En).is_some());
+
+        let last = ListQuery { page: 7, ..query };
+        let page = last.page(&websites, now);
+        assert_eq!((page.number, page.sites.len()), (2, 5));
+        assert_eq!(page.sites[0].url, "https://20.com");
+    }
+
+    #[test]
+    fn test_filter_and_sort() {
+        let now = Utc::now();
+        let mut tagged = site("https://b.com", 200, now - Duration::hours(1));
+        tagged.tags = vec!["prod".to_string()];
+        let websites = [
+            site("https://c.com", 503, now - Duration::hours(2)),
+            tagged,
+            site("https://a.com", 0, now),
+        ];
+        let urls = |args: &str| -> Vec<String> {
+            ListQuery::parse(args)
+                .unwrap()
+                .page(&websites, now)
+                .sites
+                .iter()
+                .map(|site| site.url.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            urls(""),
+            ["https://a.com", "https://b.com", "https://c.com"]
+        );
+        assert_eq!(urls("down sort:status"), ["https://a.com", "https://c.com"]);
+        assert_eq!(
+            urls("sort:changed"),
+            ["https://a.com", "https://b.com", "https://c.com"]
+        );
+        assert_eq!(urls("tag:prod"), ["https://b.com"]);
+        assert_eq!(urls("up"), ["https://b.com"]);
+        assert_eq!(urls("c.com"), ["https://c.com"]);
+        assert!(urls("tag:staging").is_empty());
+    }
+}
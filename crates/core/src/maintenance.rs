@@ -0,0 +1,353 @@
+use chrono::{DateTime, Datelike, NaiveTime, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::{Language, t};
+use crate::parse_url::extract_hostname;
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const FULL_DAY_NAMES: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// Why a maintenance window or quiet hours were refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// The first argument of a maintenance window is neither
+    NotSiteOrDays(String),
+    /// Days, a time range and a timezone are needed
+    WindowArguments,
+    /// A time range and a timezone are needed
+    HoursArguments,
+    UnknownDays(String),
+    InvalidTimeRange(String),
+    UnknownTimezone(String),
+}
+
+impl ScheduleError {
+    pub fn message(&self, language: Language) -> String {
+        match self {
+            ScheduleError::NotSiteOrDays(arg) => t!(language, "schedule.not_site", arg = arg),
+            ScheduleError::WindowArguments => t!(language, "schedule.window_arguments"),
+            ScheduleError::HoursArguments => t!(language, "schedule.hours_arguments"),
+            ScheduleError::UnknownDays(days) => t!(language, "schedule.unknown_days", days = days),
+            ScheduleError::InvalidTimeRange(times) => {
+                t!(language, "schedule.invalid_times", times = times)
+            }
+            ScheduleError::UnknownTimezone(timezone) => {
+                t!(language, "schedule.unknown_timezone", timezone = timezone)
+            }
+        }
+    }
+}
+
+/// A weekly recurring window during which failures are recorded but alerts
+/// are withheld. Without a hostname it covers every site of the chat.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MaintenanceWindow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Days the window starts on, counted from Monday = 0
+    pub days: Vec<u32>,
+    /// Minutes since local midnight; `end <= start` wraps past midnight
+    pub start: u32,
+    pub end: u32,
+    pub timezone: String,
+    /// Report sites still down once the window is over
+    #[serde(default)]
+    pub summary: bool,
+}
+
+impl MaintenanceWindow {
+    /// Parse `[site] <days> <HH:MM-HH:MM> <timezone> [summary]`, for example
+    /// `sun 02:00-04:00 Europe/Berlin` or `api.example.com mon-fri 22:00-23:00 UTC summary`.
+    pub fn parse(spec: &str) -> Result<Self, ScheduleError> {
+        let mut tokens: Vec<&str> = spec.split_whitespace().collect();
+
+        let summary = tokens
+            .last()
+            .is_some_and(|t| t.eq_ignore_ascii_case("summary"));
+        if summary {
+            tokens.pop();
+        }
+
+        let hostname = match tokens.first() {
+            Some(first) if parse_days(first).is_none() => {
+                let hostname = extract_hostname(first);
+                if hostname.len() < 3 {
+                    return Err(ScheduleError::NotSiteOrDays(first.to_string()));
+                }
+                tokens.remove(0);
+                Some(hostname)
+            }
+            _ => None,
+        };
+
+        let [days, times, timezone] = tokens[..] else {
+            return Err(ScheduleError::WindowArguments);
+        };
+        let days = parse_days(days).ok_or_else(|| ScheduleError::UnknownDays(days.to_string()))?;
+        let (start, end) = parse_time_range(times)
+            .ok_or_else(|| ScheduleError::InvalidTimeRange(times.to_string()))?;
+        let timezone: Tz = timezone
+            .parse()
+            .map_err(|_| ScheduleError::UnknownTimezone(timezone.to_string()))?;
+
+        Ok(MaintenanceWindow {
+            hostname,
+            days,
+            start,
+            end,
+            timezone: timezone.name().to_string(),
+            summary,
+        })
+    }
+
+    pub fn applies_to(&self, url: &str) -> bool {
+        self.hostname
+            .as_ref()
+            .is_none_or(|hostname| extract_hostname(url) == *hostname)
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        covers(&self.days, self.start, self.end, &self.timezone, now)
+    }
+
+    pub fn describe(&self, language: Language) -> String {
+        let days = match self.days.len() {
+            7 => t!(language, "maintenance.daily"),
+            _ => self
+                .days
+                .iter()
+                .map(|&d| DAY_NAMES[d as usize])
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        let mut description = format!(
+            "{days} {}–{} {}",
+            format_minute(self.start),
+            format_minute(self.end),
+            self.timezone
+        );
+        if let Some(hostname) = &self.hostname {
+            description = format!("{hostname}: {description}");
+        }
+        if self.summary {
+            description.push_str(&format!(" ({})", t!(language, "maintenance.summary")));
+        }
+        description
+    }
+}
+
+/// The window covering this site right now, preferring one that asks for a
+/// summary when several overlap.
+pub fn active_window<'a>(
+    windows: &'a [MaintenanceWindow],
+    url: &str,
+    now: DateTime<Utc>,
+) -> Option<&'a MaintenanceWindow> {
+    windows
+        .iter()
+        .filter(|window| window.applies_to(url) && window.is_active(now))
+        .max_by_key(|window| window.summary)
+}
+
+/// True if `now`, in the given timezone, falls between `start` and `end`
+/// (minutes since midnight) of a window starting on one of `days`.
+pub(crate) fn covers(
+    days: &[u32],
+    start: u32,
+    end: u32,
+    timezone: &str,
+    now: DateTime<Utc>,
+) -> bool {
+    let Ok(timezone) = timezone.parse::<Tz>() else {
+        return false;
+    };
+    let local = now.with_timezone(&timezone);
+    let minute = local.hour() * 60 + local.minute();
+    let today = local.weekday().num_days_from_monday();
+    let yesterday = (today + 6) % 7;
+
+    if start < end {
+        days.contains(&today) && (start..end).contains(&minute)
+    } else {
+        (days.contains(&today) && minute >= start) || (days.contains(&yesterday) && minute < end)
+    }
+}
+
+fn parse_day(name: &str) -> Option<u32> {
+    let name = name
+        .strip_suffix('s')
+        .filter(|n| n.ends_with("day"))
+        .unwrap_or(name);
+    DAY_NAMES
+        .iter()
+        .zip(FULL_DAY_NAMES.iter())
+        .position(|(short, full)| name == *short || name == *full)
+        .map(|day| day as u32)
+}
+
+// Accepts `daily`, single days, ranges and comma separated lists: `sun`,
+// `sundays`, `mon-fri`, `sat,sun`.
+fn parse_days(input: &str) -> Option<Vec<u32>> {
+    let input = input.to_lowercase();
+    if input == "daily" {
+        return Some((0..7).collect());
+    }
+
+    let mut days = Vec::new();
+    for part in input.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (parse_day(from)?, parse_day(to)?);
+                let mut day = from;
+                loop {
+                    days.push(day);
+                    if day == to {
+                        break;
+                    }
+                    day = (day + 1) % 7;
+                }
+            }
+            None => days.push(parse_day(part)?),
+        }
+    }
+    days.sort_unstable();
+    days.dedup();
+    Some(days)
+}
+
+fn parse_minute(input: &str) -> Option<u32> {
+    if input == "24:00" {
+        return Some(24 * 60);
+    }
+    let time = NaiveTime::parse_from_str(input, "%H:%M").ok()?;
+    Some(time.hour() * 60 + time.minute())
+}
+
+pub(crate) fn parse_time_range(input: &str) -> Option<(u32, u32)> {
+    let (start, end) = input.split_once(['-', '–'])?;
+    let (start, end) = (parse_minute(start)?, parse_minute(end)?);
+    if start == end || start == 24 * 60 {
+        return None;
+    }
+    Some((start, end % (24 * 60)))
+}
+
+pub(crate) fn format_minute(minute: u32) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_chat_window() {
+        let window = MaintenanceWindow::parse("Sundays 02:00–04:00 Europe/Berlin").unwrap();
+        assert_eq!(window.hostname, None);
+        assert_eq!(window.days, vec![6]);
+        assert_eq!((window.start, window.end), (120, 240));
+        assert_eq!(window.timezone, "Europe/Berlin");
+        assert!(!window.summary);
+    }
+
+    #[test]
+    fn test_parse_site_window() {
+        let window =
+            MaintenanceWindow::parse("https://api.example.com mon-fri 22:00-01:00 UTC summary")
+                .unwrap();
+        assert_eq!(window.hostname.as_deref(), Some("api.example.com"));
+        assert_eq!(window.days, vec![0, 1, 2, 3, 4]);
+        assert_eq!((window.start, window.end), (22 * 60, 60));
+        assert!(window.summary);
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("daily"), Some((0..7).collect()));
+        assert_eq!(parse_days("sat,sun"), Some(vec![5, 6]));
+        assert_eq!(parse_days("fri-mon"), Some(vec![0, 4, 5, 6]));
+        assert_eq!(parse_days("Tuesday"), Some(vec![1]));
+        assert_eq!(parse_days("monkey"), None);
+        assert_eq!(parse_days("example.com"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(MaintenanceWindow::parse("").is_err());
+        assert!(MaintenanceWindow::parse("sun 02:00-04:00").is_err());
+        assert!(MaintenanceWindow::parse("sun 02:00-02:00 UTC").is_err());
+        assert!(MaintenanceWindow::parse("sun 25:00-02:00 UTC").is_err());
+        assert!(MaintenanceWindow::parse("sun 02:00-04:00 Mars/Olympus").is_err());
+        assert!(MaintenanceWindow::parse("x sun 02:00-04:00 UTC").is_err());
+    }
+
+    #[test]
+    fn test_active_in_local_time() {
+        let window = MaintenanceWindow::parse("sun 02:00-04:00 Europe/Berlin").unwrap();
+        // 2024-06-02 is a Sunday; Berlin is UTC+2 in summer
+        assert!(!window.is_active(utc(2024, 6, 1, 23, 59)));
+        assert!(window.is_active(utc(2024, 6, 2, 0, 0)));
+        assert!(window.is_active(utc(2024, 6, 2, 1, 59)));
+        assert!(!window.is_active(utc(2024, 6, 2, 2, 0)));
+    }
+
+    #[test]
+    fn test_active_past_midnight() {
+        let window = MaintenanceWindow::parse("sat 23:00-01:00 UTC").unwrap();
+        // 2024-06-01 is a Saturday
+        assert!(window.is_active(utc(2024, 6, 1, 23, 30)));
+        assert!(window.is_active(utc(2024, 6, 2, 0, 30)));
+        assert!(!window.is_active(utc(2024, 6, 2, 1, 0)));
+        assert!(!window.is_active(utc(2024, 6, 2, 23, 30)));
+    }
+
+    #[test]
+    fn test_applies_to_site() {
+        let windows = vec![
+            MaintenanceWindow::parse("daily 00:00-24:00 UTC").unwrap(),
+            MaintenanceWindow::parse("api.example.com daily 00:00-24:00 UTC summary").unwrap(),
+        ];
+        let now = utc(2024, 6, 1, 12, 0);
+        assert_eq!(
+            active_window(&windows, "https://api.example.com", now),
+            Some(&windows[1])
+        );
+        assert_eq!(
+            active_window(&windows, "https://www.example.com", now),
+            Some(&windows[0])
+        );
+        assert_eq!(
+            active_window(&windows[1..], "https://www.example.com", now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let window =
+            MaintenanceWindow::parse("api.example.com sat,sun 02:00-04:00 UTC summary").unwrap();
+        assert_eq!(
+            window.describe(Language::En),
+            "api.example.com: sat,sun 02:00–04:00 UTC (summary)"
+        );
+        let window = MaintenanceWindow::parse("mon-sun 02:00-04:00 UTC summary").unwrap();
+        assert_eq!(
+            window.describe(Language::De),
+            "täglich 02:00–04:00 UTC (Zusammenfassung)"
+        );
+    }
+}
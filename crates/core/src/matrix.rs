@@ -0,0 +1,162 @@
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
+use url::form_urlencoded::byte_serialize;
+
+use crate::html::strip_tags;
+
+/// Room IDs look like `!opaque:server`; aliases would need resolving first.
+pub fn is_valid_room_id(room: &str) -> bool {
+    room.len() > 1 && room.starts_with('!') && room.contains(':')
+}
+
+/// Send an alert rendered for Telegram as an `m.room.message` event through the client-server
+/// API. Each request gets a fresh transaction ID, so the homeserver only
+/// deduplicates retries of the same request.
+pub fn request(
+    client: &Client,
+    homeserver: &str,
+    room: &str,
+    token: &str,
+    html: &str,
+) -> RequestBuilder {
+    let url = format!(
+        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+        homeserver.trim_end_matches('/'),
+        byte_serialize(room.as_bytes()).collect::<String>(),
+        hex::encode(rand::random::<[u8; 8]>()),
+    );
+    client.put(url).bearer_auth(token).json(&json!({
+        "msgtype": "m.text",
+        "body": strip_tags(html),
+        "format": "org.matrix.custom.html",
+        "formatted_body": html.replace('\n', "<br>"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::process;
+    use crate::channel::{Channel, ChannelNotifier};
+    use crate::i18n::Language;
+    use crate::mongo::{ChatSettings, Website};
+    use crate::notify::{Event, Notifier};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Stands in for a homeserver: records one request and answers it with
+    // the given status and an event ID
+    async fn homeserver(status: &'static str) -> (String, Arc<Mutex<String>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let request = Arc::new(Mutex::new(String::new()));
+        let log = request.clone();
+        tokio::spawn(async move {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                return;
+            };
+            let mut received = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                received.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&received).to_string();
+                let complete = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                    head.lines()
+                        .filter_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(str::to_string)
+                        })
+                        .next()
+                        .and_then(|length| length.trim().parse::<usize>().ok())
+                        .is_some_and(|length| body.len() >= length)
+                });
+                if n == 0 || complete {
+                    *log.lock().unwrap() = text;
+                    break;
+                }
+            }
+            let body = r#"{"event_id":"$event"}"#;
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+        (format!("http://{addr}"), request)
+    }
+
+    fn chat(homeserver: &str) -> ChatSettings {
+        ChatSettings {
+            chat_id: 1,
+            channels: vec![Channel::Matrix {
+                homeserver: homeserver.to_string(),
+                room: "!ops:example.org".to_string(),
+                token: "syt_secret".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn down() -> Event {
+        Event::Changed(Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(200),
+            status: 503,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_room_id() {
+        assert!(is_valid_room_id("!ops:example.org"));
+        assert!(!is_valid_room_id("#ops:example.org"));
+        assert!(!is_valid_room_id("!ops"));
+    }
+
+    #[tokio::test]
+    async fn test_sends_room_message() {
+        let (url, request) = homeserver("200 OK").await;
+        let html = process(Language::En, "https://example.com", 503);
+        let response = super::request(
+            &Client::new(),
+            &url,
+            "!ops:example.org",
+            "syt_secret",
+            &html,
+        )
+        .send()
+        .await
+        .unwrap();
+        assert!(response.status().is_success());
+
+        let request = request.lock().unwrap().clone();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(
+            head.starts_with(
+                "PUT /_matrix/client/v3/rooms/%21ops%3Aexample.org/send/m.room.message/"
+            )
+        );
+        assert!(
+            head.to_lowercase()
+                .contains("authorization: bearer syt_secret")
+        );
+
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["msgtype"], "m.text");
+        assert_eq!(body["format"], "org.matrix.custom.html");
+        assert_eq!(body["formatted_body"], html.replace('\n', "<br>"));
+        assert_eq!(body["body"], strip_tags(&html));
+    }
+
+    #[tokio::test]
+    async fn test_refuses_internal_homeserver() {
+        let (url, request) = homeserver("200 OK").await;
+        let notifier = ChannelNotifier::new(Arc::new(Client::new()));
+
+        // The token never reaches a homeserver on a loopback address
+        assert!(!notifier.notify(&chat(&url), &[down()]).await);
+        assert!(request.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::future::BoxFuture;
+use mongodb::{
+    Database,
+    bson::{self, Document, doc},
+    error::Error,
+    options::{ReplaceOptions, UpdateOptions},
+};
+
+use crate::mongo::ensure_indexes;
+
+type MigrationFn = for<'a> fn(&'a Database) -> BoxFuture<'a, Result<(), Error>>;
+
+struct Migration {
+    version: u32,
+    name: &'static str,
+    run: MigrationFn,
+}
+
+// Append new migrations to the end with the next version number; never
+// reorder or edit one that has already shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "dedupe_subscriptions",
+        run: dedupe_subscriptions,
+    },
+    Migration {
+        version: 2,
+        name: "typed_last_updated",
+        run: typed_last_updated,
+    },
+    Migration {
+        version: 3,
+        name: "numeric_chat_id",
+        run: numeric_chat_id,
+    },
+];
+
+/// Apply every migration newer than the latest recorded version, in order.
+/// Each migration must be idempotent since the poller and the webhook may
+/// start at the same time.
+pub async fn run_migrations(db: &Database) -> Result<(), Error> {
+    let applied = db.collection::<Document>("migrations");
+    let latest = applied
+        .find_one(doc! {})
+        .sort(doc! { "_id": -1 })
+        .await?
+        .and_then(|record| record.get_i64("_id").ok())
+        .unwrap_or(0);
+
+    for migration in pending(latest as u32) {
+        log::info!(
+            "Applying migration {} ({})",
+            migration.version,
+            migration.name
+        );
+        (migration.run)(db).await?;
+        applied
+            .update_one(
+                doc! { "_id": migration.version as i64 },
+                doc! { "$set": { "name": migration.name, "applied_at": bson::DateTime::now() } },
+            )
+            .with_options(UpdateOptions::builder().upsert(true).build())
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn pending(latest: u32) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS.iter().filter(move |m| m.version > latest)
+}
+
+// Drop duplicate (url, telegram_id) rows so the unique index can be built.
+fn dedupe_subscriptions(db: &Database) -> BoxFuture<'_, Result<(), Error>> {
+    Box::pin(async move {
+        let websites = db.collection::<Document>("websites");
+        let mut cursor = websites
+            .aggregate([
+                doc! { "$group": {
+                    "_id": { "url": "$url", "telegram_id": "$telegram_id" },
+                    "ids": { "$push": "$_id" },
+                    "count": { "$sum": 1 },
+                } },
+                doc! { "$match": { "count": { "$gt": 1 } } },
+            ])
+            .await?;
+
+        while cursor.advance().await? {
+            let group = cursor.deserialize_current()?;
+            let Ok(ids) = group.get_array("ids") else {
+                continue;
+            };
+            let duplicates = ids.iter().skip(1).cloned().collect::<Vec<_>>();
+            websites
+                .delete_many(doc! { "_id": { "$in": duplicates } })
+                .await?;
+        }
+
+        Ok(())
+    })
+}
+
+// Convert string last_updated values to BSON datetimes. Older writers used
+// RFC 3339 in put_site and "%Y-%m-%d %H:%M:%S" (UTC) in the poller.
+fn typed_last_updated(db: &Database) -> BoxFuture<'_, Result<(), Error>> {
+    Box::pin(async move {
+        let websites = db.collection::<Document>("websites");
+        let mut cursor = websites
+            .find(doc! { "last_updated": { "$type": "string" } })
+            .await?;
+
+        while cursor.advance().await? {
+            let website = cursor.deserialize_current()?;
+            let Ok(id) = website.get_object_id("_id") else {
+                continue;
+            };
+            let raw = website.get_str("last_updated").unwrap_or_default();
+            let timestamp = parse_legacy_timestamp(raw).unwrap_or_else(|| {
+                log::warn!("Unparseable last_updated {raw:?} on {id}, resetting to now");
+                Utc::now()
+            });
+            websites
+                .update_one(
+                    doc! { "_id": id },
+                    doc! { "$set": { "last_updated": bson::DateTime::from_chrono(timestamp) } },
+                )
+                .await?;
+        }
+
+        Ok(())
+    })
+}
+
+fn parse_legacy_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S")
+        .map(|dt| dt.and_utc())
+        .ok()
+}
+
+// Replace the string telegram_id with a numeric chat_id. Rows whose id cannot
+// be parsed can never receive an alert, so they are moved to a quarantine
+// collection where they can be fixed by hand and moved back.
+//
+// The unique (telegram_id, url) index goes first: every converted row loses
+// its telegram_id, and two chats tracking the same URL would otherwise
+// collide on a missing telegram_id halfway through.
+fn numeric_chat_id(db: &Database) -> BoxFuture<'_, Result<(), Error>> {
+    Box::pin(async move {
+        let websites = db.collection::<Document>("websites");
+        let quarantine = db.collection::<Document>(QUARANTINE);
+
+        if let Err(e) = websites.drop_index("telegram_id_url_unique").await {
+            log::info!("Legacy telegram_id index not dropped: {e}");
+        }
+        let mut cursor = websites
+            .find(doc! { "$or": [
+                { "telegram_id": { "$exists": true } },
+                { "chat_id": { "$exists": false } },
+            ] })
+            .await?;
+
+        while cursor.advance().await? {
+            let website = cursor.deserialize_current()?;
+            let Ok(id) = website.get_object_id("_id") else {
+                continue;
+            };
+            let Some(chat_id) = parse_legacy_chat_id(website.get("telegram_id")) else {
+                log::warn!("Moving website with malformed telegram_id to {QUARANTINE}: {website}");
+                quarantine
+                    .replace_one(doc! { "_id": id }, &website)
+                    .with_options(ReplaceOptions::builder().upsert(true).build())
+                    .await?;
+                websites.delete_one(doc! { "_id": id }).await?;
+                continue;
+            };
+            // The same chat under an id written both as a string and as a
+            // number; the subscription is already there
+            let url = website.get_str("url").unwrap_or_default();
+            let existing = websites
+                .count_documents(doc! { "chat_id": chat_id, "url": url, "_id": { "$ne": id } })
+                .await?;
+            if existing > 0 {
+                websites.delete_one(doc! { "_id": id }).await?;
+                continue;
+            }
+            websites
+                .update_one(
+                    doc! { "_id": id },
+                    doc! {
+                        "$set": { "chat_id": chat_id },
+                        "$unset": { "telegram_id": "" },
+                    },
+                )
+                .await?;
+        }
+
+        ensure_indexes(&websites).await
+    })
+}
+
+/// Subscriptions a migration could not convert, kept as they were.
+const QUARANTINE: &str = "quarantined_websites";
+
+fn parse_legacy_chat_id(raw: Option<&bson::Bson>) -> Option<i64> {
+    match raw? {
+        bson::Bson::String(s) => s.trim().parse().ok(),
+        bson::Bson::Int32(n) => Some(i64::from(*n)),
+        bson::Bson::Int64(n) => Some(*n),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_strictly_increasing() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert!(versions.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(versions.first(), Some(&1));
+    }
+
+    #[test]
+    fn test_pending_from_scratch() {
+        assert_eq!(pending(0).count(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_parse_legacy_rfc3339() {
+        let parsed = parse_legacy_timestamp("2024-01-01T12:30:00.123+02:00").unwrap();
+        assert_eq!(parsed.to_rfc3339(), "2024-01-01T10:30:00.123+00:00");
+    }
+
+    #[test]
+    fn test_parse_legacy_poller_format() {
+        let parsed = parse_legacy_timestamp("2024-01-01 12:30:00").unwrap();
+        assert_eq!(parsed.to_rfc3339(), "2024-01-01T12:30:00+00:00");
+    }
+
+    #[test]
+    fn test_parse_legacy_garbage() {
+        assert!(parse_legacy_timestamp("yesterday").is_none());
+        assert!(parse_legacy_timestamp("").is_none());
+    }
+
+    #[test]
+    fn test_parse_legacy_chat_id() {
+        use bson::Bson;
+
+        assert_eq!(
+            parse_legacy_chat_id(Some(&Bson::String("3000000000".into()))),
+            Some(3_000_000_000)
+        );
+        assert_eq!(
+            parse_legacy_chat_id(Some(&Bson::String("-1001234567890".into()))),
+            Some(-1_001_234_567_890)
+        );
+        assert_eq!(parse_legacy_chat_id(Some(&Bson::Int32(42))), Some(42));
+        assert_eq!(
+            parse_legacy_chat_id(Some(&Bson::String("abc".into()))),
+            None
+        );
+        assert_eq!(parse_legacy_chat_id(Some(&Bson::Null)), None);
+        assert_eq!(parse_legacy_chat_id(None), None);
+    }
+
+    #[test]
+    fn test_pending_up_to_date() {
+        let latest = MIGRATIONS.last().unwrap().version;
+        assert_eq!(pending(latest).count(), 0);
+    }
+
+    // Needs a MongoDB server to write a scratch database to
+    #[tokio::test]
+    #[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+    async fn test_numeric_chat_id_with_shared_url() {
+        use mongodb::{Client, IndexModel, options::IndexOptions};
+
+        let uri = std::env::var("MONGODB_TEST_URI").expect("MONGODB_TEST_URI must be set");
+        let client = Client::with_uri_str(uri).await.unwrap();
+        let db = client.database(&format!("mandown_test_{}", rand::random::<u32>()));
+        let websites = db.collection::<Document>("websites");
+
+        // The index and rows as the version before the migration left them
+        let legacy_index = IndexModel::builder()
+            .keys(doc! { "telegram_id": 1, "url": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("telegram_id_url_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+        websites.create_index(legacy_index).await.unwrap();
+        websites
+            .insert_many([
+                doc! { "url": "https://a.com", "telegram_id": "1" },
+                doc! { "url": "https://a.com", "telegram_id": "2" },
+                doc! { "url": "https://a.com", "telegram_id": 2 },
+            ])
+            .await
+            .unwrap();
+
+        let result = numeric_chat_id(&db).await;
+        let mut chat_ids = Vec::new();
+        let mut cursor = websites.find(doc! {}).await.unwrap();
+        while cursor.advance().await.unwrap() {
+            let website = cursor.deserialize_current().unwrap();
+            assert!(!website.contains_key("telegram_id"));
+            chat_ids.push(website.get_i64("chat_id").unwrap());
+        }
+        let indexes = websites.list_index_names().await.unwrap();
+        db.drop().await.unwrap();
+
+        result.unwrap();
+        chat_ids.sort();
+        assert_eq!(chat_ids, [1, 2]);
+        assert!(indexes.contains(&"chat_id_url_unique".to_string()));
+        assert!(!indexes.contains(&"telegram_id_url_unique".to_string()));
+    }
+}
@@ -1,22 +1,188 @@
-use chrono::Utc;
+use crate::channel::Channel;
+use crate::digest::{Change, DigestInterval};
+use crate::email::{CodeQuota, EmailSubscription};
+use crate::http::{FailureKind, Probe};
+use crate::i18n::Language;
+use crate::incident::{Incident, Transition};
+use crate::maintenance::MaintenanceWindow;
+use crate::migrate::run_migrations;
+use crate::parse_url::hostname_pattern;
+use crate::settings::{NotifyOn, QuietHours, TagNotify, wants};
+use crate::tag::SiteSelector;
+use crate::template::Templates;
+use crate::webhook::{Delivery, WebhookTarget};
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
 use mongodb::{
-    Client, Collection,
-    bson::{Document, doc},
-    options::ClientOptions,
+    Client, Collection, IndexModel,
+    bson::{Document, doc, oid::ObjectId},
+    error::{ErrorKind, WriteFailure},
+    options::{ClientOptions, IndexOptions},
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Website {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id: Option<ObjectId>,
     pub url: String,
-    pub last_updated: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub last_updated: DateTime<Utc>,
     pub status: i32,
-    pub telegram_id: String,
+    /// Status before the latest change, if it has changed since tracking began
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_status: Option<i32>,
+    /// Why the latest check got no response, when it did not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+    pub chat_id: i64,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub muted_until: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incident: Option<Incident>,
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub paused_until: Option<DateTime<Utc>>,
+    /// An alert was withheld during a maintenance window that asked for a
+    /// summary once it is over
+    #[serde(default)]
+    pub pending_summary: bool,
+    /// The chat blocked the bot or was deleted; mirrors `ChatSettings::inactive`
+    #[serde(default)]
+    pub inactive: bool,
+    /// When the poller last probed the site
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub last_checked: Option<DateTime<Utc>>,
+    /// How long the last probe took to get an answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<i64>,
+    /// Labels to group the chat's sites by, in lowercase
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// How long the outage lasted, set for the sweep in which the site recovers
+    #[serde(skip)]
+    pub outage: Option<chrono::Duration>,
+}
+
+/// Per-chat preferences, stored in the `chats` collection keyed by chat id.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChatSettings {
+    #[serde(rename = "_id")]
+    pub chat_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation: Option<Escalation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintenance: Vec<MaintenanceWindow>,
+    #[serde(default)]
+    pub notify: NotifyOn,
+    /// Notification settings for sites with certain tags
+    #[serde(default, skip_serializing_if = "TagNotify::is_empty")]
+    pub tag_notify: TagNotify,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+    /// Collect changes into a periodic digest instead of alerting right away
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<DigestInterval>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub digest_queue: Vec<Change>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub last_digest: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<EmailSubscription>,
+    #[serde(default)]
+    pub email_quota: CodeQuota,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookTarget>,
+    /// Chat apps such as Slack that also receive status changes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<Channel>,
+    /// Alert wording that replaces the built-in templates
+    #[serde(default, skip_serializing_if = "Templates::is_empty")]
+    pub templates: Templates,
+    /// Set with `/language`, or taken from whoever first talked to the bot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    /// Messages to the chat fail because it blocked the bot or is gone
+    #[serde(default)]
+    pub inactive: bool,
+}
+
+impl ChatSettings {
+    pub fn wants(&self, website: &Website) -> bool {
+        wants(
+            &self.notify,
+            &self.tag_notify,
+            &website.tags,
+            website.transition(),
+        )
+    }
+
+    /// Whether the chat hears about the site being down, for reminders and
+    /// escalations of its open incident.
+    pub fn wants_down(&self, website: &Website) -> bool {
+        wants(
+            &self.notify,
+            &self.tag_notify,
+            &website.tags,
+            Transition::Down,
+        )
+    }
+
+    /// Alerts during quiet hours are delivered without a sound
+    pub fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        self.quiet_hours
+            .as_ref()
+            .is_some_and(|quiet| quiet.is_active(now))
+    }
+}
+
+/// Where unacknowledged incidents are forwarded, and after how long.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Escalation {
+    pub chat_id: i64,
+    pub after_secs: i64,
+}
+
+impl Website {
+    pub fn is_muted(&self, now: DateTime<Utc>) -> bool {
+        self.muted_until.is_some_and(|until| until > now)
+    }
+
+    pub fn is_paused(&self, now: DateTime<Utc>) -> bool {
+        self.paused && self.paused_until.is_none_or(|until| until > now)
+    }
+
+    pub fn transition(&self) -> Transition {
+        Transition::between(self.previous_status.unwrap_or(self.status), self.status)
+    }
+
+    pub fn record_probe(&mut self, probe: &Probe, at: DateTime<Utc>) {
+        self.last_checked = Some(at);
+        // A probe that got no answer has no response time to speak of
+        self.latency_ms = match probe.failure {
+            None => Some(probe.latency.as_millis() as i64),
+            Some(_) => None,
+        };
+    }
 }
 
 pub async fn init_mongo() -> Arc<Collection<Document>> {
@@ -35,14 +201,117 @@ pub async fn init_mongo() -> Arc<Collection<Document>> {
 
     let client = Client::with_options(client_options).expect("Failed to create MongoDB client");
     let db = client.database("mandown");
-    Arc::new(db.collection::<Document>("websites"))
+    let collection = db.collection::<Document>("websites");
+
+    run_migrations(&db).await.expect("Failed to run migrations");
+    ensure_indexes(&collection)
+        .await
+        .expect("Failed to create indexes");
+
+    Arc::new(collection)
+}
+
+/// Create the indexes the queries below rely on. Safe to call on every
+/// startup: creating an index that already exists is a no-op.
+pub async fn ensure_indexes(
+    collection: &Collection<Document>,
+) -> Result<(), mongodb::error::Error> {
+    // Serves the per-chat lookups (chat_id prefix) as well as the
+    // exact (url, chat_id) match, and rejects duplicate subscriptions.
+    let subscription = IndexModel::builder()
+        .keys(doc! { "chat_id": 1, "url": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("chat_id_url_unique".to_string())
+                .unique(true)
+                .build(),
+        )
+        .build();
+
+    // Serves the sweep over incidents still waiting for acknowledgement
+    let open_incidents = IndexModel::builder()
+        .keys(doc! { "incident.acknowledged": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("incident_acknowledged".to_string())
+                .sparse(true)
+                .build(),
+        )
+        .build();
+
+    collection
+        .create_indexes([subscription, open_incidents])
+        .await?;
+
+    // The webhook delivery log only needs to cover the recent past
+    let delivery_log = IndexModel::builder()
+        .keys(doc! { "at": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("webhook_deliveries_ttl".to_string())
+                .expire_after(Duration::from_secs(7 * 24 * 60 * 60))
+                .build(),
+        )
+        .build();
+    sibling(collection, "webhook_deliveries")
+        .create_index(delivery_log)
+        .await?;
+    Ok(())
+}
+
+// Other collections live next to `websites` in the same database
+fn sibling(collection: &Collection<Document>, name: &str) -> Collection<Document> {
+    collection
+        .client()
+        .database(&collection.namespace().db)
+        .collection(name)
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == 11000
+    )
+}
+
+async fn find_site_id(
+    collection: &Collection<Document>,
+    website_url: &str,
+    chat_id: i64,
+) -> Result<Option<ObjectId>, mongodb::error::Error> {
+    let Some(existing_website) = collection
+        .find_one(doc! {
+            "url": website_url,
+            "chat_id": chat_id
+        })
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let id = existing_website
+        .get("_id")
+        .ok_or_else(|| {
+            mongodb::error::Error::from(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "No _id field found",
+            ))
+        })?
+        .as_object_id()
+        .ok_or_else(|| {
+            mongodb::error::Error::from(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid ObjectId",
+            ))
+        })?;
+    Ok(Some(id))
 }
 
 pub async fn put_site(
     collection: &Collection<Document>,
     website_url: &str,
-    user_telegram_id: i32,
-) -> Result<mongodb::bson::oid::ObjectId, mongodb::error::Error> {
+    chat_id: i64,
+) -> Result<ObjectId, mongodb::error::Error> {
     if website_url.is_empty() {
         return Err(mongodb::error::Error::from(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
@@ -51,50 +320,34 @@ pub async fn put_site(
     }
 
     // Check if website already exists for this user
-    if let Some(existing_website) = collection
-        .find_one(doc! {
-            "url": website_url,
-            "telegram_id": user_telegram_id.to_string()
-        })
-        .await?
-    {
-        let id = existing_website
-            .get("_id")
-            .ok_or_else(|| {
-                mongodb::error::Error::from(std::io::Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "No _id field found",
-                ))
-            })?
-            .as_object_id()
-            .ok_or_else(|| {
-                mongodb::error::Error::from(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Invalid ObjectId",
-                ))
-            })?;
+    if let Some(id) = find_site_id(collection, website_url, chat_id).await? {
         return Ok(id);
     }
 
     // Create new website document
     let new_website = doc! {
         "url": website_url,
-        "last_updated": Utc::now().to_rfc3339(),
+        "last_updated": bson::DateTime::now(),
         "status": 200,
-        "telegram_id": user_telegram_id.to_string()
+        "chat_id": chat_id
     };
 
-    // Insert the new website
-    let result = collection.insert_one(new_website).await?;
-    Ok(result.inserted_id.as_object_id().unwrap())
+    // Insert the new website; a concurrent /track may have won the race
+    match collection.insert_one(new_website).await {
+        Ok(result) => Ok(result.inserted_id.as_object_id().unwrap()),
+        Err(e) if is_duplicate_key(&e) => find_site_id(collection, website_url, chat_id)
+            .await?
+            .ok_or(e),
+        Err(e) => Err(e),
+    }
 }
 
-pub async fn clear_user_websites(
+pub async fn clear_chat_websites(
     collection: &Collection<Document>,
-    user_telegram_id: i32,
+    chat_id: i64,
 ) -> Result<u64, mongodb::error::Error> {
     let filter = doc! {
-        "telegram_id": user_telegram_id.to_string()
+        "chat_id": chat_id
     };
 
     let result = collection.delete_many(filter).await?;
@@ -104,21 +357,503 @@ pub async fn clear_user_websites(
 pub async fn delete_sites_by_hostname(
     collection: &Collection<Document>,
     hostname: &str,
-    user_telegram_id: i32,
+    chat_id: i64,
 ) -> Result<u64, mongodb::error::Error> {
     if hostname.len() < 3 {
         return Ok(0);
     }
-    let pattern = format!("://{hostname}");
     let filter = doc! {
-        "url": { "$regex": pattern },
-        "telegram_id": user_telegram_id.to_string()
+        "url": { "$regex": hostname_pattern(hostname) },
+        "chat_id": chat_id
     };
 
     let result = collection.delete_many(filter).await?;
     Ok(result.deleted_count)
 }
 
+pub async fn get_site(
+    collection: &Collection<Document>,
+    id: ObjectId,
+    chat_id: i64,
+) -> Result<Option<Website>, mongodb::error::Error> {
+    let filter = doc! { "_id": id, "chat_id": chat_id };
+    match collection.find_one(filter).await? {
+        Some(doc) => Ok(Some(mongodb::bson::from_document(doc)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn delete_site(
+    collection: &Collection<Document>,
+    id: ObjectId,
+    chat_id: i64,
+) -> Result<u64, mongodb::error::Error> {
+    let result = collection
+        .delete_one(doc! { "_id": id, "chat_id": chat_id })
+        .await?;
+    Ok(result.deleted_count)
+}
+
+pub async fn mute_site(
+    collection: &Collection<Document>,
+    id: ObjectId,
+    chat_id: i64,
+    until: DateTime<Utc>,
+) -> Result<u64, mongodb::error::Error> {
+    let result = collection
+        .update_one(
+            doc! { "_id": id, "chat_id": chat_id },
+            doc! { "$set": { "muted_until": bson::DateTime::from_chrono(until) } },
+        )
+        .await?;
+    Ok(result.matched_count)
+}
+
+/// Acknowledge open incidents in a chat, either for one site or for every
+/// site on a hostname. An empty hostname acknowledges all of them.
+pub async fn acknowledge_incidents(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    site: Option<ObjectId>,
+    hostname: &str,
+) -> Result<u64, mongodb::error::Error> {
+    let mut filter = doc! {
+        "chat_id": chat_id,
+        "incident.acknowledged": false,
+    };
+    if let Some(id) = site {
+        filter.insert("_id", id);
+    } else if !hostname.is_empty() {
+        filter.insert("url", doc! { "$regex": hostname_pattern(hostname) });
+    }
+
+    let result = collection
+        .update_many(filter, doc! { "$set": { "incident.acknowledged": true } })
+        .await?;
+    Ok(result.modified_count)
+}
+
+pub async fn get_open_incidents(
+    collection: &Collection<Document>,
+) -> Result<Vec<Website>, mongodb::error::Error> {
+    let mut cursor = collection
+        .find(doc! { "incident.acknowledged": false, "inactive": { "$ne": true } })
+        .await?;
+
+    let mut websites = Vec::new();
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        if let Ok(website) = mongodb::bson::from_document::<Website>(doc) {
+            websites.push(website);
+        }
+    }
+    Ok(websites)
+}
+
+pub async fn save_incident(
+    collection: &Collection<Document>,
+    website: &Website,
+) -> Result<(), mongodb::error::Error> {
+    if let Some(id) = website.id {
+        collection
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "incident": bson::to_bson(&website.incident)? } },
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn get_chat_settings(
+    collection: &Collection<Document>,
+    chat_id: i64,
+) -> Result<ChatSettings, mongodb::error::Error> {
+    let chats = sibling(collection, "chats");
+    match chats.find_one(doc! { "_id": chat_id }).await? {
+        Some(doc) => Ok(mongodb::bson::from_document(doc)?),
+        None => Ok(ChatSettings {
+            chat_id,
+            ..Default::default()
+        }),
+    }
+}
+
+// Set one field of a chat's settings, or remove it when `value` is `None`
+async fn set_chat_field<T: Serialize + ?Sized>(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    field: &str,
+    value: Option<&T>,
+) -> Result<(), mongodb::error::Error> {
+    let chats = sibling(collection, "chats");
+    let update = match value {
+        Some(value) => doc! { "$set": { field: bson::to_bson(value)? } },
+        None => doc! { "$unset": { field: "" } },
+    };
+    chats
+        .update_one(doc! { "_id": chat_id }, update)
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_escalation(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    escalation: Option<&Escalation>,
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "escalation", escalation).await
+}
+
+pub async fn set_notify(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    notify: &NotifyOn,
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "notify", Some(notify)).await
+}
+
+pub async fn set_tag_notify(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    tag_notify: &TagNotify,
+) -> Result<(), mongodb::error::Error> {
+    let tag_notify = (!tag_notify.is_empty()).then_some(tag_notify);
+    set_chat_field(collection, chat_id, "tag_notify", tag_notify).await
+}
+
+pub async fn set_quiet_hours(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    quiet_hours: Option<&QuietHours>,
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "quiet_hours", quiet_hours).await
+}
+
+pub async fn set_digest(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    digest: Option<DigestInterval>,
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "digest", digest.as_ref()).await
+}
+
+pub async fn set_email(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    email: Option<&EmailSubscription>,
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "email", email).await
+}
+
+pub async fn set_email_quota(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    quota: &CodeQuota,
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "email_quota", Some(quota)).await
+}
+
+pub async fn set_webhooks(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    webhooks: &[WebhookTarget],
+) -> Result<(), mongodb::error::Error> {
+    let webhooks = (!webhooks.is_empty()).then_some(webhooks);
+    set_chat_field(collection, chat_id, "webhooks", webhooks).await
+}
+
+pub async fn set_channels(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    channels: &[Channel],
+) -> Result<(), mongodb::error::Error> {
+    let channels = (!channels.is_empty()).then_some(channels);
+    set_chat_field(collection, chat_id, "channels", channels).await
+}
+
+pub async fn set_language(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    language: Language,
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "language", Some(&language)).await
+}
+
+pub async fn set_templates(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    templates: &Templates,
+) -> Result<(), mongodb::error::Error> {
+    let templates = (!templates.is_empty()).then_some(templates);
+    set_chat_field(collection, chat_id, "templates", templates).await
+}
+
+pub async fn log_webhook_delivery(
+    collection: &Collection<Document>,
+    delivery: &Delivery,
+) -> Result<(), mongodb::error::Error> {
+    sibling(collection, "webhook_deliveries")
+        .insert_one(bson::to_document(delivery)?)
+        .await?;
+    Ok(())
+}
+
+/// The latest webhook deliveries of a chat, newest first.
+pub async fn get_webhook_deliveries(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    limit: i64,
+) -> Result<Vec<Delivery>, mongodb::error::Error> {
+    let mut cursor = sibling(collection, "webhook_deliveries")
+        .find(doc! { "chat_id": chat_id })
+        .sort(doc! { "at": -1 })
+        .limit(limit)
+        .await?;
+
+    let mut deliveries = Vec::new();
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        if let Ok(delivery) = mongodb::bson::from_document::<Delivery>(doc) {
+            deliveries.push(delivery);
+        }
+    }
+    Ok(deliveries)
+}
+
+/// Append changes to a chat's digest queue.
+pub async fn queue_digest(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    changes: &[Change],
+) -> Result<(), mongodb::error::Error> {
+    let chats = sibling(collection, "chats");
+    chats
+        .update_one(
+            doc! { "_id": chat_id },
+            doc! { "$push": { "digest_queue": { "$each": bson::to_bson(changes)? } } },
+        )
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+/// Settings of every chat with changes waiting for a digest.
+pub async fn get_queued_digests(
+    collection: &Collection<Document>,
+) -> Result<Vec<ChatSettings>, mongodb::error::Error> {
+    let chats = sibling(collection, "chats");
+    let mut cursor = chats
+        .find(doc! { "digest_queue.0": { "$exists": true }, "inactive": { "$ne": true } })
+        .await?;
+
+    let mut settings = Vec::new();
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        if let Ok(chat) = mongodb::bson::from_document::<ChatSettings>(doc) {
+            settings.push(chat);
+        }
+    }
+    Ok(settings)
+}
+
+/// Drop the changes that were just sent, keeping any queued since.
+pub async fn clear_digest(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    sent: usize,
+    sent_at: DateTime<Utc>,
+) -> Result<(), mongodb::error::Error> {
+    let chats = sibling(collection, "chats");
+    chats
+        .update_one(
+            doc! { "_id": chat_id },
+            vec![doc! { "$set": {
+                "digest_queue": { "$slice": [
+                    "$digest_queue",
+                    sent as i64,
+                    { "$max": [{ "$size": "$digest_queue" }, 1] },
+                ] },
+                "last_digest": bson::DateTime::from_chrono(sent_at),
+            } }],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Stop checking the sites of a chat that can no longer be reached.
+pub async fn deactivate_chat(
+    collection: &Collection<Document>,
+    chat_id: i64,
+) -> Result<(), mongodb::error::Error> {
+    let chats = sibling(collection, "chats");
+    chats
+        .update_one(
+            doc! { "_id": chat_id },
+            doc! { "$set": { "inactive": true } },
+        )
+        .upsert(true)
+        .await?;
+    collection
+        .update_many(
+            doc! { "chat_id": chat_id },
+            doc! { "$set": { "inactive": true } },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Resume checking the sites of a chat that was inactive. Returns whether
+/// the chat had been marked inactive.
+pub async fn reactivate_chat(
+    collection: &Collection<Document>,
+    chat_id: i64,
+) -> Result<bool, mongodb::error::Error> {
+    let chats = sibling(collection, "chats");
+    let result = chats
+        .update_one(
+            doc! { "_id": chat_id, "inactive": true },
+            doc! { "$unset": { "inactive": "" } },
+        )
+        .await?;
+    if result.modified_count == 0 {
+        return Ok(false);
+    }
+    collection
+        .update_many(
+            doc! { "chat_id": chat_id },
+            doc! { "$unset": { "inactive": "" } },
+        )
+        .await?;
+    Ok(true)
+}
+
+// The sites of a chat on a hostname or with a tag
+fn selector_filter(selector: &SiteSelector, chat_id: i64) -> Document {
+    match selector {
+        SiteSelector::Hostname(hostname) => doc! {
+            "url": { "$regex": hostname_pattern(hostname) },
+            "chat_id": chat_id
+        },
+        SiteSelector::Tag(tag) => doc! { "tags": tag, "chat_id": chat_id },
+    }
+}
+
+/// Pause every selected site in a chat, indefinitely when `until` is
+/// `None`. Paused sites keep their record but are left out of the sweep.
+pub async fn pause_sites(
+    collection: &Collection<Document>,
+    selector: &SiteSelector,
+    chat_id: i64,
+    until: Option<DateTime<Utc>>,
+) -> Result<u64, mongodb::error::Error> {
+    let filter = selector_filter(selector, chat_id);
+    let update = match until {
+        Some(until) => doc! { "$set": {
+            "paused": true,
+            "paused_until": bson::DateTime::from_chrono(until),
+        } },
+        None => doc! {
+            "$set": { "paused": true },
+            "$unset": { "paused_until": "" },
+        },
+    };
+
+    let result = collection.update_many(filter, update).await?;
+    Ok(result.matched_count)
+}
+
+pub async fn resume_sites(
+    collection: &Collection<Document>,
+    selector: &SiteSelector,
+    chat_id: i64,
+) -> Result<u64, mongodb::error::Error> {
+    let mut filter = selector_filter(selector, chat_id);
+    filter.insert("paused", true);
+
+    let result = collection.update_many(filter, resume_update()).await?;
+    Ok(result.modified_count)
+}
+
+/// Replace the tags of every site on a hostname in a chat; no tags removes
+/// them.
+pub async fn set_tags_by_hostname(
+    collection: &Collection<Document>,
+    hostname: &str,
+    chat_id: i64,
+    tags: &[String],
+) -> Result<u64, mongodb::error::Error> {
+    let filter = selector_filter(&SiteSelector::Hostname(hostname.to_string()), chat_id);
+    let update = if tags.is_empty() {
+        doc! { "$unset": { "tags": "" } }
+    } else {
+        doc! { "$set": { "tags": tags } }
+    };
+
+    let result = collection.update_many(filter, update).await?;
+    Ok(result.matched_count)
+}
+
+/// Resume every site whose pause has run out.
+pub async fn resume_expired_pauses(
+    collection: &Collection<Document>,
+    now: DateTime<Utc>,
+) -> Result<u64, mongodb::error::Error> {
+    let filter = doc! {
+        "paused": true,
+        "paused_until": { "$lte": bson::DateTime::from_chrono(now) }
+    };
+
+    let result = collection.update_many(filter, resume_update()).await?;
+    Ok(result.modified_count)
+}
+
+fn resume_update() -> Document {
+    doc! {
+        "$set": { "paused": false },
+        "$unset": { "paused_until": "" },
+    }
+}
+
+pub async fn set_maintenance_windows(
+    collection: &Collection<Document>,
+    chat_id: i64,
+    windows: &[MaintenanceWindow],
+) -> Result<(), mongodb::error::Error> {
+    set_chat_field(collection, chat_id, "maintenance", Some(&windows)).await
+}
+
+pub async fn get_pending_summaries(
+    collection: &Collection<Document>,
+) -> Result<Vec<Website>, mongodb::error::Error> {
+    let mut cursor = collection
+        .find(doc! { "pending_summary": true, "inactive": { "$ne": true } })
+        .await?;
+
+    let mut websites = Vec::new();
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        if let Ok(website) = mongodb::bson::from_document::<Website>(doc) {
+            websites.push(website);
+        }
+    }
+    Ok(websites)
+}
+
+pub async fn clear_pending_summary(
+    collection: &Collection<Document>,
+    id: ObjectId,
+) -> Result<(), mongodb::error::Error> {
+    collection
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": { "pending_summary": false } },
+        )
+        .await?;
+    Ok(())
+}
+
 pub async fn get_sites(
     collection: &Collection<Document>,
     skip: u64,
@@ -129,7 +864,10 @@ pub async fn get_sites(
         .skip(skip)
         .limit(limit)
         .build();
-    let mut cursor = collection.find(doc! {}).with_options(find_options).await?;
+    let mut cursor = collection
+        .find(doc! { "paused": { "$ne": true }, "inactive": { "$ne": true } })
+        .with_options(find_options)
+        .await?;
 
     while cursor.advance().await? {
         let doc = cursor.deserialize_current()?;
@@ -141,6 +879,32 @@ pub async fn get_sites(
     Ok(websites)
 }
 
+/// Save when each site was probed and how fast it answered, whether or not
+/// its status changed.
+pub async fn record_checks(
+    collection: &Collection<Document>,
+    websites: &[Website],
+) -> Result<(), mongodb::error::Error> {
+    for website in websites {
+        let (Some(id), Some(checked)) = (website.id, website.last_checked) else {
+            continue;
+        };
+        collection
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": {
+                        "last_checked": bson::DateTime::from_chrono(checked),
+                        "latency_ms": website.latency_ms,
+                    },
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
 pub async fn update_db(
     collection: &Collection<Document>,
     websites: &[Website],
@@ -153,7 +917,11 @@ pub async fn update_db(
                     doc! {
                         "$set": {
                             "status": website.status,
-                            "last_updated": &website.last_updated,
+                            "previous_status": website.previous_status,
+                            "failure": bson::to_bson(&website.failure)?,
+                            "last_updated": bson::DateTime::from_chrono(website.last_updated),
+                            "incident": bson::to_bson(&website.incident)?,
+                            "pending_summary": website.pending_summary,
                         },
                     },
                 )
@@ -164,11 +932,11 @@ pub async fn update_db(
     Ok(())
 }
 
-pub async fn get_user_websites(
+pub async fn get_chat_websites(
     collection: &Collection<Document>,
-    telegram_id: i32,
+    chat_id: i64,
 ) -> Result<Vec<Website>, mongodb::error::Error> {
-    let filter = doc! { "telegram_id": format!("{telegram_id}") };
+    let filter = doc! { "chat_id": chat_id };
 
     let mut cursor = collection.find(filter).await.map_err(|e| {
         log::error!("Failed to query MongoDB: {e}");
@@ -0,0 +1,234 @@
+use mongodb::Collection;
+use mongodb::bson::Document;
+use std::sync::Arc;
+use teloxide::Bot;
+
+use crate::alert::TelegramNotifier;
+use crate::channel::ChannelNotifier;
+use crate::digest::Change;
+use crate::email::{EmailNotifier, SmtpConfig};
+use crate::mongo::{ChatSettings, Website};
+use crate::webhook::WebhookNotifier;
+
+/// Something the poller tells a chat about.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The site's status changed in this sweep
+    Changed(Website),
+    /// The site's incident is still open and nobody acknowledged it
+    Reminder(Website),
+    /// The site is still down after a maintenance window that asked for a
+    /// summary
+    MaintenanceOver(Website),
+    /// The incident went unacknowledged for too long and is forwarded to the
+    /// chat's escalation target
+    Escalated { website: Website, target: i64 },
+    /// Changes collected since the chat's last digest
+    Digest(Vec<Change>),
+}
+
+/// A channel alerts are delivered through. Each notifier picks the events it
+/// handles and ignores the rest, and decides from the chat's settings
+/// whether the chat subscribed to it at all.
+#[async_trait::async_trait]
+pub trait Notifier: Send + Sync {
+    /// Deliver one chat's events, returning false if any of those this
+    /// notifier handles could not be delivered.
+    async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool;
+
+    /// Whether chats in digest mode get their changes from this notifier as
+    /// an `Event::Digest` rather than one by one.
+    fn digests(&self) -> bool {
+        false
+    }
+
+    /// Called once the sweep is done.
+    async fn finish(&self) {}
+}
+
+/// Fans each chat's events out to every channel.
+#[derive(Default)]
+pub struct Notifiers(Vec<Box<dyn Notifier>>);
+
+impl Notifiers {
+    pub fn new(notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        Notifiers(notifiers)
+    }
+
+    /// Telegram, plus email when SMTP is configured, plus webhooks and chat
+    /// apps.
+    pub fn standard(
+        bot: Bot,
+        collection: &Collection<Document>,
+        client: Arc<reqwest::Client>,
+    ) -> Self {
+        let mut notifiers = Self::external(collection, client);
+        notifiers
+            .0
+            .insert(0, Box::new(TelegramNotifier::new(bot, collection.clone())));
+        notifiers
+    }
+
+    /// Every channel but Telegram, for changes the bot already reports in
+    /// the chat itself.
+    pub fn external(collection: &Collection<Document>, client: Arc<reqwest::Client>) -> Self {
+        let mut notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(WebhookNotifier::new(collection.clone())),
+            Box::new(ChannelNotifier::new(client)),
+        ];
+        match SmtpConfig::from_env() {
+            Ok(Some(config)) => notifiers.push(Box::new(EmailNotifier::new(config))),
+            Ok(None) => {}
+            Err(e) => log::error!("Not sending email alerts: {e}"),
+        }
+        Notifiers(notifiers)
+    }
+
+    pub async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+        let mut delivered = true;
+        for notifier in &self.0 {
+            delivered &= notifier.notify(chat, events).await;
+        }
+        delivered
+    }
+
+    /// Send a chat's digest through the notifiers that collect one.
+    pub async fn notify_digest(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+        self.notify_where(chat, events, true).await
+    }
+
+    /// Send the changes of a chat in digest mode to the notifiers that do
+    /// not collect a digest, so they still hear of each one right away.
+    pub async fn notify_undigested(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+        self.notify_where(chat, events, false).await
+    }
+
+    async fn notify_where(&self, chat: &ChatSettings, events: &[Event], digests: bool) -> bool {
+        let mut delivered = true;
+        for notifier in self.0.iter().filter(|n| n.digests() == digests) {
+            delivered &= notifier.notify(chat, events).await;
+        }
+        delivered
+    }
+
+    pub async fn finish(&self) {
+        for notifier in &self.0 {
+            notifier.finish().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Keeps every event it is given
+    #[derive(Default, Clone)]
+    struct RecordingNotifier {
+        events: Arc<Mutex<Vec<(i64, Event)>>>,
+    }
+
+    impl RecordingNotifier {
+        fn events(&self) -> Vec<(i64, Event)> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Notifier for RecordingNotifier {
+        async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+            let mut recorded = self.events.lock().unwrap();
+            recorded.extend(events.iter().map(|event| (chat.chat_id, event.clone())));
+            true
+        }
+    }
+
+    struct FailingNotifier;
+
+    #[async_trait::async_trait]
+    impl Notifier for FailingNotifier {
+        async fn notify(&self, _: &ChatSettings, _: &[Event]) -> bool {
+            false
+        }
+    }
+
+    // Collects digests, like the Telegram notifier
+    struct DigestNotifier(RecordingNotifier);
+
+    #[async_trait::async_trait]
+    impl Notifier for DigestNotifier {
+        async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+            self.0.notify(chat, events).await
+        }
+
+        fn digests(&self) -> bool {
+            true
+        }
+    }
+
+    fn chat(chat_id: i64) -> ChatSettings {
+        ChatSettings {
+            chat_id,
+            ..Default::default()
+        }
+    }
+
+    fn changed(url: &str) -> Event {
+        Event::Changed(Website {
+            url: url.to_string(),
+            status: 503,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fans_out_to_every_notifier() {
+        let (first, second) = (RecordingNotifier::default(), RecordingNotifier::default());
+        let notifiers = Notifiers::new(vec![Box::new(first.clone()), Box::new(second.clone())]);
+
+        assert!(notifiers.notify(&chat(1), &[changed("a.com")]).await);
+        assert!(notifiers.notify(&chat(2), &[changed("b.com")]).await);
+
+        for recorder in [first, second] {
+            let events = recorder.events();
+            assert_eq!(events.len(), 2);
+            assert!(matches!(&events[0], (1, Event::Changed(site)) if site.url == "a.com"));
+            assert!(matches!(&events[1], (2, Event::Changed(site)) if site.url == "b.com"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_failure_but_still_notifies_the_rest() {
+        let recorder = RecordingNotifier::default();
+        let notifiers = Notifiers::new(vec![Box::new(FailingNotifier), Box::new(recorder.clone())]);
+
+        assert!(!notifiers.notify(&chat(1), &[changed("a.com")]).await);
+        assert_eq!(recorder.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_digest_chats() {
+        let (digest, other) = (RecordingNotifier::default(), RecordingNotifier::default());
+        let notifiers = Notifiers::new(vec![
+            Box::new(DigestNotifier(digest.clone())),
+            Box::new(other.clone()),
+            Box::new(FailingNotifier),
+        ]);
+
+        // Failing notifiers that do not collect digests do not hold one up
+        assert!(
+            notifiers
+                .notify_digest(&chat(1), &[Event::Digest(Vec::new())])
+                .await
+        );
+        assert!(
+            !notifiers
+                .notify_undigested(&chat(1), &[changed("a.com")])
+                .await
+        );
+
+        assert!(matches!(&digest.events()[..], [(1, Event::Digest(_))]));
+        assert!(matches!(&other.events()[..], [(1, Event::Changed(_))]));
+    }
+}
@@ -0,0 +1,103 @@
+use reqwest::{Client, RequestBuilder};
+
+use crate::channel::{headline, severity, status_line};
+use crate::i18n::Language;
+use crate::incident::{Transition, is_down};
+use crate::mongo::Website;
+
+// Emoji shortcodes ntfy shows in front of the title, followed by plain tags
+fn tags(website: &Website) -> &'static str {
+    match (website.transition(), is_down(website.status)) {
+        (Transition::Down, _) => "red_circle,down",
+        (Transition::Recovery, _) => "green_circle,up",
+        (Transition::StatusChange, true) => "orange_circle,down",
+        (Transition::StatusChange, false) => "yellow_circle,up",
+    }
+}
+
+/// Publish to a topic URL such as `https://ntfy.sh/mandown`, with the
+/// details in ntfy's headers so no JSON endpoint is needed.
+pub fn request(
+    client: &Client,
+    url: &str,
+    token: Option<&str>,
+    website: &Website,
+    language: Language,
+) -> RequestBuilder {
+    let request = client
+        .post(url)
+        .header("Title", headline(website, language))
+        .header("Priority", severity(website).to_string())
+        .header("Tags", tags(website))
+        .header("Click", &website.url)
+        .body(status_line(website, language, ""));
+    match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::FailureKind;
+
+    #[test]
+    fn test_request() {
+        let website = Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(200),
+            status: 0,
+            failure: Some(FailureKind::Dns),
+            ..Default::default()
+        };
+        let request = request(
+            &Client::new(),
+            "https://ntfy.sh/mandown",
+            Some("tk_secret"),
+            &website,
+            Language::En,
+        )
+        .build()
+        .unwrap();
+        let header = |name: &str| request.headers()[name].to_str().unwrap().to_string();
+
+        assert_eq!(request.url().as_str(), "https://ntfy.sh/mandown");
+        assert_eq!(header("Title"), "https://example.com is down");
+        assert_eq!(header("Priority"), "5");
+        assert_eq!(header("Tags"), "red_circle,down");
+        assert_eq!(header("Click"), "https://example.com");
+        assert_eq!(header("Authorization"), "Bearer tk_secret");
+        assert_eq!(
+            request.body().unwrap().as_bytes().unwrap(),
+            b"Status: 0 (was 200), DNS lookup failed"
+        );
+    }
+
+    #[test]
+    fn test_recovery_without_token() {
+        let website = Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(503),
+            status: 200,
+            ..Default::default()
+        };
+        let request = request(
+            &Client::new(),
+            "http://ntfy.example.org/up",
+            None,
+            &website,
+            Language::De,
+        )
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            request.headers()["Title"],
+            "https://example.com ist wieder erreichbar"
+        );
+        assert_eq!(request.headers()["Priority"], "2");
+        assert_eq!(request.headers()["Tags"], "green_circle,up");
+        assert!(!request.headers().contains_key("Authorization"));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+use teloxide::{ApiError, RequestError, prelude::*, requests::Request};
+use tokio::time::{Instant, sleep};
+
+const MAX_ATTEMPTS: u32 = 4;
+
+// Telegram's documented limits: about 30 messages per second overall, one
+// per second to the same chat, and 20 per minute to the same group
+const GLOBAL_LIMIT: (usize, Duration) = (30, Duration::from_secs(1));
+const CHAT_LIMIT: (usize, Duration) = (1, Duration::from_secs(1));
+const GROUP_LIMIT: (usize, Duration) = (20, Duration::from_secs(60));
+
+/// Messages sent by the poller in one sweep.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryStats {
+    pub sent: usize,
+    /// Sent, but only after at least one failed attempt
+    pub retried: usize,
+    pub failed: usize,
+}
+
+impl fmt::Display for DeliveryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Delivered {} messages ({} after a retry), {} failed",
+            self.sent, self.retried, self.failed
+        )
+    }
+}
+
+/// Spaces out messages to stay within Telegram's rate limits, so that a sweep
+/// with many alerts is delivered rather than rejected.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    sent: VecDeque<(Instant, ChatId)>,
+}
+
+impl RateLimiter {
+    /// How long to wait before a message to `chat` may go out.
+    pub(crate) fn wait(&self, chat: ChatId, now: Instant) -> Duration {
+        let limit = |(count, window): (usize, Duration), only_chat: bool| {
+            let recent: Vec<Instant> = self
+                .sent
+                .iter()
+                .filter(|(at, to)| now - *at < window && (!only_chat || *to == chat))
+                .map(|(at, _)| *at)
+                .collect();
+            match recent.len().checked_sub(count) {
+                Some(over) => (recent[over] + window).saturating_duration_since(now),
+                None => Duration::ZERO,
+            }
+        };
+
+        let mut wait = limit(GLOBAL_LIMIT, false).max(limit(CHAT_LIMIT, true));
+        // Supergroups and channels too, which `is_group` leaves out
+        if !chat.is_user() {
+            wait = wait.max(limit(GROUP_LIMIT, true));
+        }
+        wait
+    }
+
+    pub(crate) fn record(&mut self, chat: ChatId, now: Instant) {
+        while self
+            .sent
+            .front()
+            .is_some_and(|(at, _)| now - *at >= GROUP_LIMIT.1)
+        {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((now, chat));
+    }
+}
+
+// The chat is gone or no longer accepts messages from the bot
+fn is_unreachable(error: &RequestError) -> bool {
+    matches!(
+        error,
+        RequestError::Api(
+            ApiError::BotBlocked
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::ChatNotFound
+                | ApiError::GroupDeactivated
+                | ApiError::UserDeactivated
+        )
+    )
+}
+
+fn is_transient(error: &RequestError) -> bool {
+    matches!(error, RequestError::Network(_) | RequestError::Io(_))
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt)
+}
+
+/// Delivers the poller's messages: waits out rate limits, honours
+/// `RetryAfter` and retries network failures with exponential backoff.
+pub struct Outbox {
+    bot: Bot,
+    limiter: RateLimiter,
+    stats: DeliveryStats,
+    unreachable: Vec<ChatId>,
+}
+
+impl Outbox {
+    pub fn new(bot: Bot) -> Self {
+        Outbox {
+            bot,
+            limiter: RateLimiter::default(),
+            stats: DeliveryStats::default(),
+            unreachable: Vec::new(),
+        }
+    }
+
+    pub fn bot(&self) -> &Bot {
+        &self.bot
+    }
+
+    pub fn stats(&self) -> DeliveryStats {
+        self.stats
+    }
+
+    /// Chats that blocked the bot or no longer exist.
+    pub fn unreachable(&self) -> &[ChatId] {
+        &self.unreachable
+    }
+
+    /// Send a request to `chat`, returning whether it was delivered.
+    pub async fn send<R>(&mut self, chat: ChatId, request: R) -> bool
+    where
+        R: Request<Err = RequestError>,
+    {
+        if self.unreachable.contains(&chat) {
+            self.stats.failed += 1;
+            return false;
+        }
+
+        let mut attempt = 0;
+        loop {
+            sleep(self.limiter.wait(chat, Instant::now())).await;
+            self.limiter.record(chat, Instant::now());
+
+            let error = match request.send_ref().await {
+                Ok(_) => {
+                    self.stats.sent += 1;
+                    if attempt > 0 {
+                        self.stats.retried += 1;
+                    }
+                    return true;
+                }
+                Err(e) => e,
+            };
+
+            let delay = match &error {
+                RequestError::RetryAfter(delay) => Some(*delay),
+                e if is_transient(e) => Some(backoff(attempt)),
+                _ => None,
+            };
+            attempt += 1;
+            match delay {
+                Some(delay) if attempt < MAX_ATTEMPTS => {
+                    log::info!("Sending to {chat} failed ({error}), retrying in {delay:?}");
+                    sleep(delay).await;
+                }
+                _ => {
+                    log::info!("Failed to send message to {chat}: {error}");
+                    if is_unreachable(&error) {
+                        self.unreachable.push(chat);
+                    }
+                    self.stats.failed += 1;
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_message_per_second_per_chat() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::default();
+        assert_eq!(limiter.wait(ChatId(1), now), Duration::ZERO);
+
+        limiter.record(ChatId(1), now);
+        assert_eq!(limiter.wait(ChatId(1), now), Duration::from_secs(1));
+        assert_eq!(
+            limiter.wait(ChatId(1), now + Duration::from_millis(400)),
+            Duration::from_millis(600)
+        );
+        assert_eq!(limiter.wait(ChatId(2), now), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_global_limit() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::default();
+        for chat in 0..30 {
+            limiter.record(ChatId(chat), now);
+        }
+        assert_eq!(limiter.wait(ChatId(100), now), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_group_limit() {
+        let start = Instant::now();
+        for group in [ChatId(-100123), ChatId(-1001234567890)] {
+            let mut limiter = RateLimiter::default();
+            for second in 0..20 {
+                limiter.record(group, start + Duration::from_secs(second));
+            }
+            let now = start + Duration::from_secs(20);
+            assert_eq!(limiter.wait(group, now), Duration::from_secs(40));
+            assert_eq!(limiter.wait(ChatId(5), now), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_unreachable_errors() {
+        assert!(is_unreachable(&RequestError::Api(ApiError::BotBlocked)));
+        assert!(is_unreachable(&RequestError::Api(ApiError::ChatNotFound)));
+        assert!(!is_unreachable(&RequestError::Api(
+            ApiError::MessageIsTooLong
+        )));
+        assert!(!is_unreachable(&RequestError::RetryAfter(
+            Duration::from_secs(3)
+        )));
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_stats_display() {
+        let stats = DeliveryStats {
+            sent: 12,
+            retried: 2,
+            failed: 1,
+        };
+        assert_eq!(
+            stats.to_string(),
+            "Delivered 12 messages (2 after a retry), 1 failed"
+        );
+    }
+}
@@ -42,6 +42,12 @@ pub fn extract_hostname(input: &str) -> String {
     }
 }
 
+/// A regex matching URLs on exactly this host, and not on hosts that merely
+/// start with it such as `example.com.evil.org`.
+pub fn hostname_pattern(hostname: &str) -> String {
+    format!("://{}(:|/|$)", regex::escape(hostname))
+}
+
 pub fn read_url(input: &str) -> (bool, String, String) {
     let url = extract_hostname(input);
     if url.is_empty() {
@@ -62,7 +68,27 @@ pub fn read_url(input: &str) -> (bool, String, String) {
 
 #[cfg(test)]
 mod tests {
-    use super::extract_hostname;
+    use super::{extract_hostname, hostname_pattern};
+    use regex::Regex;
+
+    #[test]
+    fn test_hostname_pattern() {
+        let pattern = Regex::new(&hostname_pattern("example.com")).unwrap();
+        for url in [
+            "https://example.com",
+            "http://example.com/path",
+            "https://example.com:8443",
+        ] {
+            assert!(pattern.is_match(url), "{url}");
+        }
+        for url in [
+            "https://example.com.evil.org",
+            "https://exampleXcom",
+            "https://example.community",
+        ] {
+            assert!(!pattern.is_match(url), "{url}");
+        }
+    }
 
     #[test]
     fn test_empty_string() {
@@ -1,10 +1,19 @@
-use crate::alert::alert_users;
 use crate::baseline::baseline_available;
-use crate::http::{find_changed_websites, get_status};
-use crate::mongo::{Website, get_sites, update_db};
+use crate::digest::{Change, digest_due};
+use crate::http::{Probe, find_changed_websites, probe_site};
+use crate::incident::{Incident, is_down, track_incident};
+use crate::maintenance::active_window;
+use crate::mongo::{
+    ChatSettings, Website, clear_digest, clear_pending_summary, get_chat_settings,
+    get_open_incidents, get_pending_summaries, get_queued_digests, get_sites, queue_digest,
+    record_checks, resume_expired_pauses, save_incident, update_db,
+};
+use crate::notify::{Event, Notifiers};
+use chrono::{DateTime, Duration, Utc};
 use futures::future::join_all;
 use mongodb::Collection;
 use mongodb::bson::Document;
+use std::collections::{HashMap, hash_map::Entry};
 use std::sync::Arc;
 use teloxide::Bot;
 use tokio::time;
@@ -21,10 +30,10 @@ pub fn start_downtime_checker(
 
     tokio::spawn(async move {
         let collection = collection;
-        let bot = bot;
         let client = http_client;
+        let notifiers = Notifiers::standard(bot, &collection, client.clone());
         loop {
-            run_once(&collection, bot.clone(), client.clone()).await;
+            run_once(&collection, &notifiers, client.clone()).await;
             time::sleep(time::Duration::from_secs(interval)).await;
         }
     });
@@ -33,11 +42,74 @@ pub fn start_downtime_checker(
 /// Run a single downtime-check sweep: fetch all tracked sites, alert on
 /// status changes, and persist the new statuses. Used by the one-shot
 /// poller binary (Cloud Run + Cloud Scheduler).
-pub async fn run_once(collection: &Collection<Document>, bot: Bot, client: Arc<reqwest::Client>) {
+pub async fn run_once(
+    collection: &Collection<Document>,
+    notifiers: &Notifiers,
+    client: Arc<reqwest::Client>,
+) {
     log::info!("Starting downtime check");
+    match resume_expired_pauses(collection, Utc::now()).await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Resumed {count} websites whose pause ran out"),
+        Err(e) => log::error!("Error resuming paused websites: {e}"),
+    }
     let changed_websites = get_changed_sites(collection, client.clone()).await;
     log::info!("Found {} changed websites", changed_websites.len());
-    handle_changed_websites(collection, bot, &changed_websites).await;
+    let mut settings = SettingsCache::default();
+    handle_changed_websites(collection, notifiers, &changed_websites, &mut settings).await;
+    handle_open_incidents(collection, notifiers, &mut settings).await;
+    handle_pending_summaries(collection, notifiers, &mut settings).await;
+    handle_digests(collection, notifiers).await;
+    notifiers.finish().await;
+}
+
+// Chat settings are looked up at most once per sweep
+#[derive(Default)]
+struct SettingsCache(HashMap<i64, ChatSettings>);
+
+impl SettingsCache {
+    async fn get(
+        &mut self,
+        collection: &Collection<Document>,
+        chat_id: i64,
+    ) -> Option<&ChatSettings> {
+        if let Entry::Vacant(entry) = self.0.entry(chat_id) {
+            match get_chat_settings(collection, chat_id).await {
+                Ok(chat) => entry.insert(chat),
+                Err(e) => {
+                    log::error!("Error getting settings for chat {chat_id}: {e}");
+                    return None;
+                }
+            };
+        }
+        self.0.get(&chat_id)
+    }
+
+    // Chats whose settings could not be read are notified with the defaults
+    async fn get_or_default(
+        &mut self,
+        collection: &Collection<Document>,
+        chat_id: i64,
+    ) -> ChatSettings {
+        match self.get(collection, chat_id).await {
+            Some(chat) => chat.clone(),
+            None => ChatSettings {
+                chat_id,
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn in_maintenance(
+        &mut self,
+        collection: &Collection<Document>,
+        website: &Website,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.get(collection, website.chat_id)
+            .await
+            .is_some_and(|chat| active_window(&chat.maintenance, &website.url, now).is_some())
+    }
 }
 
 async fn get_changed_sites(
@@ -54,7 +126,7 @@ async fn get_changed_sites(
     const LIMIT: i64 = 20;
 
     loop {
-        let websites = match get_sites(collection, skip, LIMIT).await {
+        let mut websites = match get_sites(collection, skip, LIMIT).await {
             Ok(sites) => sites,
             Err(e) => {
                 log::error!("Error getting websites from DB: {e}");
@@ -68,7 +140,16 @@ async fn get_changed_sites(
 
         log::info!("Getting statuses for {} websites", websites.len());
 
-        let new_statuses = fetch_website_statuses(&websites, client.clone()).await;
+        let probes = fetch_website_probes(&websites, client.clone()).await;
+        let checked = Utc::now();
+        for (website, probe) in websites.iter_mut().zip(&probes) {
+            website.failure = probe.failure;
+            website.record_probe(probe, checked);
+        }
+        if let Err(e) = record_checks(collection, &websites).await {
+            log::error!("Error saving check times: {e}");
+        }
+        let new_statuses: Vec<u16> = probes.iter().map(|probe| probe.status).collect();
         let changed_in_batch = find_changed_websites(&websites, &new_statuses);
         all_changed_websites.extend(changed_in_batch);
 
@@ -78,15 +159,27 @@ async fn get_changed_sites(
     all_changed_websites
 }
 
-async fn fetch_website_statuses(websites: &[Website], client: Arc<reqwest::Client>) -> Vec<u16> {
-    let status_futures = websites.iter().map(|web| get_status(&client, &web.url));
-    join_all(status_futures).await
+async fn fetch_website_probes(websites: &[Website], client: Arc<reqwest::Client>) -> Vec<Probe> {
+    let probe_futures = websites.iter().map(|web| probe_site(&client, &web.url));
+    join_all(probe_futures).await
+}
+
+/// Alert on and save status changes found outside a sweep, such as by a
+/// re-check from an alert's button.
+pub async fn report_changes(
+    collection: &Collection<Document>,
+    notifiers: &Notifiers,
+    changed_websites: &[Website],
+) {
+    let mut settings = SettingsCache::default();
+    handle_changed_websites(collection, notifiers, changed_websites, &mut settings).await;
 }
 
 async fn handle_changed_websites(
     collection: &Collection<Document>,
-    bot: Bot,
+    notifiers: &Notifiers,
     changed_websites: &[Website],
+    settings: &mut SettingsCache,
 ) {
     let web_count = changed_websites.len();
 
@@ -94,9 +187,314 @@ async fn handle_changed_websites(
         return;
     }
 
-    alert_users(bot, changed_websites).await;
+    let now = Utc::now();
+    let mut changed_websites = changed_websites.to_vec();
+    let mut to_alert: Vec<(i64, Vec<Event>)> = Vec::new();
+    let mut to_queue: HashMap<i64, Vec<Change>> = HashMap::new();
+    let mut undigested: Vec<(i64, Vec<Event>)> = Vec::new();
+    for website in &mut changed_websites {
+        track_incident(website, now);
+
+        // Muted sites and sites under maintenance still get their new status
+        // saved, just without an alert
+        if website.is_muted(now) {
+            continue;
+        }
+        let Some(chat) = settings.get(collection, website.chat_id).await else {
+            push_event(
+                &mut to_alert,
+                website.chat_id,
+                Event::Changed(website.clone()),
+            );
+            continue;
+        };
+        if let Some(window) = active_window(&chat.maintenance, &website.url, now) {
+            website.pending_summary |= window.summary;
+            continue;
+        }
+        if !chat.wants(website) {
+            continue;
+        }
+        match chat.digest {
+            Some(_) => {
+                to_queue
+                    .entry(website.chat_id)
+                    .or_default()
+                    .push(Change::from(&*website));
+                push_event(
+                    &mut undigested,
+                    website.chat_id,
+                    Event::Changed(website.clone()),
+                );
+            }
+            None => push_event(
+                &mut to_alert,
+                website.chat_id,
+                Event::Changed(website.clone()),
+            ),
+        }
+    }
+
+    for (chat_id, events) in to_alert {
+        let chat = settings.get_or_default(collection, chat_id).await;
+        notifiers.notify(&chat, &events).await;
+    }
 
-    if let Err(e) = update_db(collection, changed_websites).await {
+    for (chat_id, events) in undigested {
+        let chat = settings.get_or_default(collection, chat_id).await;
+        notifiers.notify_undigested(&chat, &events).await;
+    }
+
+    for (chat_id, changes) in to_queue {
+        if let Err(e) = queue_digest(collection, chat_id, &changes).await {
+            log::error!("Error queueing digest for chat {chat_id}: {e}");
+        }
+    }
+
+    if let Err(e) = update_db(collection, &changed_websites).await {
         log::error!("Error updating websites in DB: {e}");
     }
 }
+
+// Keeps each chat's events together, in the order the chats first came up
+fn push_event(events: &mut Vec<(i64, Vec<Event>)>, chat_id: i64, event: Event) {
+    match events.iter_mut().find(|(id, _)| *id == chat_id) {
+        Some((_, chat_events)) => chat_events.push(event),
+        None => events.push((chat_id, vec![event])),
+    }
+}
+
+/// Remind chats about incidents they have not acknowledged, and forward them
+/// to the chat's escalation target once they have been open long enough.
+async fn handle_open_incidents(
+    collection: &Collection<Document>,
+    notifiers: &Notifiers,
+    settings: &mut SettingsCache,
+) {
+    let interval = dotenvy::var("RENOTIFY_INTERVAL")
+        .unwrap_or("1800".to_string())
+        .parse()
+        .expect("RENOTIFY_INTERVAL must be a number");
+    let interval = Duration::seconds(interval);
+
+    let websites = match get_open_incidents(collection).await {
+        Ok(websites) => websites,
+        Err(e) => {
+            log::error!("Error getting open incidents from DB: {e}");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    for mut website in websites {
+        if website.is_muted(now)
+            || website.is_paused(now)
+            || settings.in_maintenance(collection, &website, now).await
+        {
+            continue;
+        }
+        let Some(mut incident) = website.incident.clone() else {
+            continue;
+        };
+        let Some(chat) = settings.get(collection, website.chat_id).await else {
+            continue;
+        };
+
+        let events = incident_events(chat, &website, &mut incident, now, interval);
+        if !events.is_empty() {
+            notifiers.notify(chat, &events).await;
+        }
+
+        if website.incident.as_ref() != Some(&incident) {
+            website.incident = Some(incident);
+            if let Err(e) = save_incident(collection, &website).await {
+                log::error!("Error saving incident for {}: {e}", website.url);
+            }
+        }
+    }
+}
+
+/// The escalation and reminder due for a site's open incident, marking them
+/// as sent. Sites whose down alerts the chat turned off, for the chat or for
+/// one of the site's tags, get neither. Chats reading a digest get no
+/// reminders, since they asked not to be alerted right away.
+fn incident_events(
+    chat: &ChatSettings,
+    website: &Website,
+    incident: &mut Incident,
+    now: DateTime<Utc>,
+    interval: Duration,
+) -> Vec<Event> {
+    let mut events = Vec::new();
+    if !chat.wants_down(website) {
+        return events;
+    }
+    if let Some(escalation) = &chat.escalation
+        && incident.escalation_due(now, Duration::seconds(escalation.after_secs))
+    {
+        events.push(Event::Escalated {
+            website: website.clone(),
+            target: escalation.chat_id,
+        });
+        incident.escalated = true;
+    }
+
+    if chat.digest.is_none() && incident.reminder_due(now, interval) {
+        events.push(Event::Reminder(website.clone()));
+        incident.last_notified = now;
+    }
+    events
+}
+
+/// Once a maintenance window that asked for a summary is over, report the
+/// sites that are still down.
+async fn handle_pending_summaries(
+    collection: &Collection<Document>,
+    notifiers: &Notifiers,
+    settings: &mut SettingsCache,
+) {
+    let websites = match get_pending_summaries(collection).await {
+        Ok(websites) => websites,
+        Err(e) => {
+            log::error!("Error getting pending maintenance summaries from DB: {e}");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    for website in websites {
+        if settings.in_maintenance(collection, &website, now).await {
+            continue;
+        }
+        if is_down(website.status)
+            && !website.is_paused(now)
+            && let Some(chat) = settings.get(collection, website.chat_id).await
+        {
+            let events = [Event::MaintenanceOver(website.clone())];
+            notifiers.notify(chat, &events).await;
+        }
+        if let Some(id) = website.id
+            && let Err(e) = clear_pending_summary(collection, id).await
+        {
+            log::error!(
+                "Error clearing maintenance summary for {}: {e}",
+                website.url
+            );
+        }
+    }
+}
+
+/// Send the digests that are due, and flush what is left for chats that
+/// have since switched back to instant alerts.
+async fn handle_digests(collection: &Collection<Document>, notifiers: &Notifiers) {
+    let chats = match get_queued_digests(collection).await {
+        Ok(chats) => chats,
+        Err(e) => {
+            log::error!("Error getting queued digests from DB: {e}");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    for chat in chats {
+        if !digest_due(chat.digest, chat.last_digest, &chat.digest_queue, now) {
+            continue;
+        }
+        let events = [Event::Digest(chat.digest_queue.clone())];
+        if !notifiers.notify_digest(&chat, &events).await {
+            continue;
+        }
+        if let Err(e) = clear_digest(collection, chat.chat_id, chat.digest_queue.len(), now).await {
+            log::error!("Error clearing digest for chat {}: {e}", chat.chat_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::digest::DigestInterval;
+    use crate::mongo::Escalation;
+    use crate::settings::NotifyOn;
+
+    fn chat() -> ChatSettings {
+        ChatSettings {
+            escalation: Some(Escalation {
+                chat_id: 2,
+                after_secs: 600,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn staging_site() -> Website {
+        Website {
+            url: "https://staging.example.com".to_string(),
+            status: 503,
+            tags: vec!["staging".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_incident_events() {
+        let now = Utc::now();
+        let mut incident = Incident::open(now - Duration::hours(1));
+        let events = incident_events(
+            &chat(),
+            &staging_site(),
+            &mut incident,
+            now,
+            Duration::minutes(30),
+        );
+
+        assert!(matches!(
+            &events[..],
+            [Event::Escalated { target: 2, .. }, Event::Reminder(_)]
+        ));
+        assert!(incident.escalated);
+        assert_eq!(incident.last_notified, now);
+    }
+
+    #[test]
+    fn test_no_reminders_in_digest_mode() {
+        let now = Utc::now();
+        let mut chat = chat();
+        chat.digest = Some(DigestInterval::Hourly);
+        let started = now - Duration::hours(1);
+        let mut incident = Incident::open(started);
+
+        let events = incident_events(
+            &chat,
+            &staging_site(),
+            &mut incident,
+            now,
+            Duration::minutes(30),
+        );
+        // The escalation goes to another chat, which is not in digest mode
+        assert!(matches!(&events[..], [Event::Escalated { .. }]));
+        assert_eq!(incident.last_notified, started);
+    }
+
+    #[test]
+    fn test_incident_events_follow_tag_settings() {
+        let now = Utc::now();
+        let mut chat = chat();
+        let quiet = NotifyOn {
+            down: false,
+            ..Default::default()
+        };
+        chat.tag_notify.insert("staging".to_string(), quiet);
+        let mut incident = Incident::open(now - Duration::hours(1));
+
+        let events = incident_events(
+            &chat,
+            &staging_site(),
+            &mut incident,
+            now,
+            Duration::minutes(30),
+        );
+        assert!(events.is_empty());
+        assert!(!incident.escalated);
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::i18n::{Language, t};
+use crate::incident::Transition;
+use crate::maintenance::{ScheduleError, covers, format_minute, parse_time_range};
+
+const EVERY_DAY: [u32; 7] = [0, 1, 2, 3, 4, 5, 6];
+
+/// Why a notification setting was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyError {
+    UnknownKind(String),
+}
+
+impl NotifyError {
+    pub fn message(&self, language: Language) -> String {
+        match self {
+            NotifyError::UnknownKind(kind) => t!(language, "settings.unknown_kind", kind = kind),
+        }
+    }
+}
+
+/// Which kinds of status change a chat wants to hear about.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct NotifyOn {
+    pub down: bool,
+    pub recovery: bool,
+    pub status_change: bool,
+}
+
+impl Default for NotifyOn {
+    fn default() -> Self {
+        NotifyOn {
+            down: true,
+            recovery: true,
+            status_change: true,
+        }
+    }
+}
+
+impl NotifyOn {
+    pub fn allows(&self, transition: Transition) -> bool {
+        match transition {
+            Transition::Down => self.down,
+            Transition::Recovery => self.recovery,
+            Transition::StatusChange => self.status_change,
+        }
+    }
+
+    /// Turn one kind on or off by the name used in `/settings`.
+    pub fn set(&mut self, kind: &str, enabled: bool) -> Result<(), NotifyError> {
+        match kind {
+            "down" => self.down = enabled,
+            "recovery" => self.recovery = enabled,
+            "change" => self.status_change = enabled,
+            _ => return Err(NotifyError::UnknownKind(kind.to_string())),
+        }
+        Ok(())
+    }
+}
+
+/// Notification settings of sites with a certain tag, where they differ
+/// from the chat's own.
+pub type TagNotify = BTreeMap<String, NotifyOn>;
+
+/// Whether to alert about a site with `tags`. Sites whose tags have their
+/// own settings follow those, and are alerted about if any of them allows it.
+pub fn wants(
+    notify: &NotifyOn,
+    by_tag: &TagNotify,
+    tags: &[String],
+    transition: Transition,
+) -> bool {
+    let mut overrides = tags.iter().filter_map(|tag| by_tag.get(tag)).peekable();
+    match overrides.peek() {
+        None => notify.allows(transition),
+        Some(_) => overrides.any(|notify| notify.allows(transition)),
+    }
+}
+
+/// A daily period in the chat's timezone during which alerts are delivered
+/// silently.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QuietHours {
+    /// Minutes since local midnight; `end <= start` wraps past midnight
+    pub start: u32,
+    pub end: u32,
+    pub timezone: String,
+}
+
+impl QuietHours {
+    /// Parse `<HH:MM-HH:MM> <timezone>`, for example `22:00-07:00 Europe/Berlin`.
+    pub fn parse(spec: &str) -> Result<Self, ScheduleError> {
+        let [times, timezone] = spec.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(ScheduleError::HoursArguments);
+        };
+        let (start, end) = parse_time_range(times)
+            .ok_or_else(|| ScheduleError::InvalidTimeRange(times.to_string()))?;
+        let timezone: Tz = timezone
+            .parse()
+            .map_err(|_| ScheduleError::UnknownTimezone(timezone.to_string()))?;
+
+        Ok(QuietHours {
+            start,
+            end,
+            timezone: timezone.name().to_string(),
+        })
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        covers(&EVERY_DAY, self.start, self.end, &self.timezone, now)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{}–{} {}",
+            format_minute(self.start),
+            format_minute(self.end),
+            self.timezone
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_notify_defaults_to_everything() {
+        let notify = NotifyOn::default();
+        assert!(notify.allows(Transition::Down));
+        assert!(notify.allows(Transition::Recovery));
+        assert!(notify.allows(Transition::StatusChange));
+    }
+
+    #[test]
+    fn test_notify_set() {
+        let mut notify = NotifyOn::default();
+        notify.set("change", false).unwrap();
+        assert!(!notify.allows(Transition::StatusChange));
+        assert!(notify.allows(Transition::Down));
+        assert!(notify.set("sideways", true).is_err());
+    }
+
+    #[test]
+    fn test_wants_follows_tags() {
+        let mut quiet = NotifyOn::default();
+        quiet.set("down", false).unwrap();
+        let by_tag = TagNotify::from([("staging".to_string(), quiet)]);
+        let tags = |tags: &[&str]| -> Vec<String> { tags.iter().map(|t| t.to_string()).collect() };
+        let chat = NotifyOn::default();
+
+        assert!(wants(&chat, &by_tag, &tags(&[]), Transition::Down));
+        assert!(wants(&chat, &by_tag, &tags(&["prod"]), Transition::Down));
+        assert!(!wants(
+            &chat,
+            &by_tag,
+            &tags(&["staging"]),
+            Transition::Down
+        ));
+        assert!(wants(
+            &chat,
+            &by_tag,
+            &tags(&["staging"]),
+            Transition::Recovery
+        ));
+
+        let by_tag = TagNotify::from([
+            ("staging".to_string(), quiet),
+            ("prod".to_string(), NotifyOn::default()),
+        ]);
+        assert!(wants(
+            &chat,
+            &by_tag,
+            &tags(&["prod", "staging"]),
+            Transition::Down
+        ));
+    }
+
+    #[test]
+    fn test_quiet_hours_overnight() {
+        let quiet = QuietHours::parse("22:00-07:00 Europe/Berlin").unwrap();
+        // Berlin is UTC+1 in winter
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 1, 15, h, m, 0).unwrap();
+        assert!(!quiet.is_active(at(20, 59)));
+        assert!(quiet.is_active(at(21, 0)));
+        assert!(quiet.is_active(at(2, 0)));
+        assert!(quiet.is_active(at(5, 59)));
+        assert!(!quiet.is_active(at(6, 0)));
+        assert_eq!(quiet.describe(), "22:00–07:00 Europe/Berlin");
+    }
+
+    #[test]
+    fn test_quiet_hours_parse_errors() {
+        assert!(QuietHours::parse("").is_err());
+        assert!(QuietHours::parse("22:00-07:00").is_err());
+        assert!(QuietHours::parse("22:00 Europe/Berlin").is_err());
+        assert!(QuietHours::parse("22:00-07:00 Nowhere/Land").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use serde_json::{Value, json};
+
+use crate::channel::{colour, headline, status_line};
+use crate::i18n::Language;
+use crate::mongo::Website;
+
+// Slack's mrkdwn only needs these three escaped
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A Block Kit message inside a coloured attachment, so the side bar shows
+/// whether the site went down or came back.
+pub fn payload(website: &Website, language: Language) -> Value {
+    let headline = headline(website, language);
+    let status = status_line(website, language, "*");
+
+    json!({
+        "text": headline,
+        "attachments": [{
+            "color": format!("#{:06x}", colour(website.transition())),
+            "blocks": [
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": format!(
+                            "*<{}|{}>*\n{status}",
+                            website.url,
+                            escape(&headline)
+                        ),
+                    },
+                },
+                {
+                    "type": "context",
+                    "elements": [{
+                        "type": "mrkdwn",
+                        "text": format!("ManDown · {}", website.last_updated.format("%Y-%m-%d %H:%M UTC")),
+                    }],
+                },
+            ],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::FailureKind;
+
+    #[test]
+    fn test_down_payload() {
+        let website = Website {
+            url: "https://example.com/?a=1&b=2".to_string(),
+            previous_status: Some(200),
+            status: 0,
+            failure: Some(FailureKind::Timeout),
+            ..Default::default()
+        };
+        let payload = payload(&website, Language::En);
+        let attachment = &payload["attachments"][0];
+
+        assert_eq!(payload["text"], "https://example.com/?a=1&b=2 is down");
+        assert_eq!(attachment["color"], "#d92d20");
+        assert_eq!(
+            attachment["blocks"][0]["text"]["text"],
+            "*<https://example.com/?a=1&b=2|https://example.com/?a=1&amp;b=2 is down>*\nStatus: *0* (was 200), timed out"
+        );
+    }
+
+    #[test]
+    fn test_recovery_colour() {
+        let website = Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(503),
+            status: 200,
+            ..Default::default()
+        };
+        let payload = payload(&website, Language::De);
+        assert_eq!(payload["attachments"][0]["color"], "#12b76a");
+        assert_eq!(payload["text"], "https://example.com ist wieder erreichbar");
+    }
+}
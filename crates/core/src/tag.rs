@@ -0,0 +1,119 @@
+use std::fmt;
+
+use crate::i18n::{Language, t};
+use crate::parse_url::extract_hostname;
+
+pub const MAX_TAGS: usize = 10;
+
+const MAX_TAG_LEN: usize = 20;
+
+/// Why tags were refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagError {
+    Invalid(String),
+    TooMany,
+}
+
+impl TagError {
+    pub fn message(&self, language: Language) -> String {
+        match self {
+            TagError::Invalid(tag) => t!(language, "tag.invalid", tag = tag, max = MAX_TAG_LEN),
+            TagError::TooMany => t!(language, "tag.too_many", max = MAX_TAGS),
+        }
+    }
+}
+
+/// Tags are lowercase letters, digits, `-` and `_`, so they read the same in
+/// `/list tag:prod` and in button payloads. A leading `#` is dropped.
+pub fn parse_tag(word: &str) -> Result<String, TagError> {
+    let tag = word.trim_start_matches('#').to_lowercase();
+    let valid = tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if tag.is_empty() || tag.len() > MAX_TAG_LEN || !valid {
+        return Err(TagError::Invalid(word.to_string()));
+    }
+    Ok(tag)
+}
+
+/// Parse the tags of `/tag <site> <tags...>`, sorted and without duplicates.
+pub fn parse_tags(words: &[&str]) -> Result<Vec<String>, TagError> {
+    let mut tags = words
+        .iter()
+        .map(|word| parse_tag(word))
+        .collect::<Result<Vec<_>, _>>()?;
+    tags.sort();
+    tags.dedup();
+    if tags.len() > MAX_TAGS {
+        return Err(TagError::TooMany);
+    }
+    Ok(tags)
+}
+
+/// The sites a bulk command such as `/pause` applies to: those on a
+/// hostname, or those with a tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SiteSelector {
+    Hostname(String),
+    Tag(String),
+}
+
+impl SiteSelector {
+    /// `tag:staging` selects by tag, anything else by the URL's hostname.
+    pub fn parse(arg: &str) -> Option<Self> {
+        if let Some(tag) = arg.strip_prefix("tag:") {
+            return parse_tag(tag).ok().map(SiteSelector::Tag);
+        }
+        let hostname = extract_hostname(arg);
+        (hostname.len() >= 3).then_some(SiteSelector::Hostname(hostname))
+    }
+}
+
+impl fmt::Display for SiteSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteSelector::Hostname(hostname) => write!(f, "{hostname}"),
+            SiteSelector::Tag(tag) => write!(f, "tag:{tag}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(&["Prod", "#api", "prod"]),
+            Ok(vec!["api".to_string(), "prod".to_string()])
+        );
+        assert!(parse_tags(&["pro d"]).is_err());
+        assert!(parse_tags(&["<b>"]).is_err());
+        assert!(parse_tags(&["tag:prod"]).is_err());
+        assert!(parse_tags(&["#"]).is_err());
+        assert!(parse_tags(&[&"a".repeat(21)]).is_err());
+
+        let many: Vec<String> = (0..11).map(|n| format!("t{n}")).collect();
+        let many: Vec<&str> = many.iter().map(String::as_str).collect();
+        assert!(parse_tags(&many).is_err());
+    }
+
+    #[test]
+    fn test_selector() {
+        assert_eq!(
+            SiteSelector::parse("tag:Staging"),
+            Some(SiteSelector::Tag("staging".to_string()))
+        );
+        assert_eq!(
+            SiteSelector::parse("https://example.com/path"),
+            Some(SiteSelector::Hostname("example.com".to_string()))
+        );
+        assert_eq!(SiteSelector::parse("tag:"), None);
+        assert_eq!(SiteSelector::parse("a"), None);
+        assert_eq!(
+            SiteSelector::Tag("prod".to_string()).to_string(),
+            "tag:prod"
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use teloxide::utils::html::escape;
+
+use crate::duration::format_duration;
+use crate::html::check_html;
+use crate::i18n::{Language, tr};
+use crate::mongo::Website;
+
+/// Names that can appear as `{{name}}` or `{{#if name}}…{{/if}}` in a
+/// template.
+pub const VARIABLES: [&str; 5] = ["site", "status", "old_status", "failure", "duration"];
+
+/// Alerts pick their template by the class of the site's new status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusClass {
+    Unreachable,
+    Up,
+    ClientError,
+    ServerError,
+    Other,
+}
+
+impl StatusClass {
+    pub const ALL: [StatusClass; 5] = [
+        StatusClass::Unreachable,
+        StatusClass::Up,
+        StatusClass::ClientError,
+        StatusClass::ServerError,
+        StatusClass::Other,
+    ];
+
+    pub fn of(status: i32) -> Self {
+        match status {
+            0 | 1 => StatusClass::Unreachable,
+            200..=299 => StatusClass::Up,
+            400..=499 => StatusClass::ClientError,
+            500..=599 => StatusClass::ServerError,
+            _ => StatusClass::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusClass::Unreachable => "unreachable",
+            StatusClass::Up => "up",
+            StatusClass::ClientError => "4xx",
+            StatusClass::ServerError => "5xx",
+            StatusClass::Other => "other",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|class| class.name().eq_ignore_ascii_case(name))
+    }
+
+    /// A status of this class, for previews.
+    pub fn example_status(self) -> i32 {
+        match self {
+            StatusClass::Unreachable => 0,
+            StatusClass::Up => 200,
+            StatusClass::ClientError => 404,
+            StatusClass::ServerError => 503,
+            StatusClass::Other => 302,
+        }
+    }
+
+    fn default_template(self, language: Language) -> &'static str {
+        tr(language, &format!("template.{}", self.name()))
+    }
+}
+
+// The field holding a class's template, borrowed mutably when `mut` is given
+macro_rules! slot {
+    ($templates:expr, $class:expr $(, $mut:tt)?) => {
+        match $class {
+            StatusClass::Unreachable => &$($mut)? $templates.unreachable,
+            StatusClass::Up => &$($mut)? $templates.up,
+            StatusClass::ClientError => &$($mut)? $templates.client_error,
+            StatusClass::ServerError => &$($mut)? $templates.server_error,
+            StatusClass::Other => &$($mut)? $templates.other,
+        }
+    };
+}
+
+/// A chat's own alert templates. Classes without an override use the
+/// built-in template of the chat's language.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Templates {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unreachable: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other: Option<String>,
+}
+
+impl Templates {
+    pub fn is_empty(&self) -> bool {
+        *self == Templates::default()
+    }
+
+    pub fn get(&self, class: StatusClass) -> Option<&str> {
+        slot!(self, class).as_deref()
+    }
+
+    /// Override a class's template, or go back to the built-in one with
+    /// `None`. Templates are checked before they are stored, both with
+    /// every variable set and with none, so that each `{{#if}}` branch
+    /// still gives HTML that Telegram accepts.
+    pub fn set(&mut self, class: StatusClass, template: Option<&str>) -> Result<(), String> {
+        if let Some(template) = template {
+            let status = class.example_status();
+            for alert in [
+                AlertContext::sample(status),
+                AlertContext::new(SAMPLE_SITE, status),
+            ] {
+                check_html(&render(template, &alert)?)?;
+            }
+        }
+        *slot!(self, class, mut) = template.map(str::to_string);
+        Ok(())
+    }
+
+    pub fn render(&self, alert: &AlertContext, language: Language) -> String {
+        let class = StatusClass::of(alert.status);
+        let builtin = class.default_template(language);
+        let template = self.get(class).unwrap_or(builtin);
+        // Stored templates were checked when set, but fall back rather than
+        // lose the alert if one is broken anyway
+        render(template, alert)
+            .unwrap_or_else(|_| render(builtin, alert).expect("built-in templates are valid"))
+    }
+}
+
+const SAMPLE_SITE: &str = "https://example.com";
+
+/// The values a template can refer to.
+#[derive(Debug, Clone, Default)]
+pub struct AlertContext {
+    pub site: String,
+    pub status: i32,
+    pub old_status: Option<i32>,
+    pub failure: Option<String>,
+    pub duration: Option<String>,
+}
+
+impl AlertContext {
+    pub fn new(site: &str, status: i32) -> Self {
+        AlertContext {
+            site: site.to_string(),
+            status,
+            ..Default::default()
+        }
+    }
+
+    /// An alert with every variable set, for checking templates.
+    fn sample(status: i32) -> Self {
+        AlertContext {
+            site: SAMPLE_SITE.to_string(),
+            status,
+            old_status: Some(200),
+            failure: Some("timeout".to_string()),
+            duration: Some("5m".to_string()),
+        }
+    }
+
+    /// The duration is how long the site has been down, or how long the
+    /// outage lasted when it just recovered.
+    pub fn from_website(website: &Website, now: DateTime<Utc>, language: Language) -> Self {
+        let duration = match &website.incident {
+            Some(incident) => Some(now - incident.started_at),
+            None => website.outage,
+        };
+        AlertContext {
+            site: website.url.clone(),
+            status: website.status,
+            old_status: website.previous_status,
+            failure: website
+                .failure
+                .map(|failure| failure.describe(language).to_string()),
+            duration: duration.map(format_duration),
+        }
+    }
+
+    fn value(&self, name: &str) -> String {
+        match name {
+            "site" => escape(&self.site),
+            "status" => self.status.to_string(),
+            "old_status" => self.old_status.map(|s| s.to_string()).unwrap_or_default(),
+            "failure" => self.failure.as_deref().map(escape).unwrap_or_default(),
+            "duration" => self.duration.as_deref().map(escape).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    Variable(&'a str),
+    If(&'a str),
+    EndIf,
+}
+
+fn variable(name: &str) -> Result<&str, String> {
+    if VARIABLES.contains(&name) {
+        Ok(name)
+    } else {
+        Err(format!("Unknown variable {{{{{name}}}}}"))
+    }
+}
+
+fn parse(template: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut in_if = false;
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find("}}") else {
+            return Err("A {{ is never closed".to_string());
+        };
+        let tag = rest[start + 2..start + end].trim();
+        rest = &rest[start + end + 2..];
+
+        if let Some(name) = tag.strip_prefix("#if ") {
+            if in_if {
+                return Err("{{#if}} blocks cannot be nested".to_string());
+            }
+            in_if = true;
+            tokens.push(Token::If(variable(name.trim())?));
+        } else if tag == "/if" {
+            if !in_if {
+                return Err("{{/if}} without a matching {{#if}}".to_string());
+            }
+            in_if = false;
+            tokens.push(Token::EndIf);
+        } else {
+            tokens.push(Token::Variable(variable(tag)?));
+        }
+    }
+    if in_if {
+        return Err("An {{#if}} is missing its {{/if}}".to_string());
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    Ok(tokens)
+}
+
+/// Fill in a template. Values are HTML-escaped; the template itself is
+/// HTML as Telegram understands it.
+pub fn render(template: &str, alert: &AlertContext) -> Result<String, String> {
+    let mut output = String::new();
+    let mut skipping = false;
+    for token in parse(template)? {
+        match token {
+            Token::If(name) => skipping = alert.value(name).is_empty(),
+            Token::EndIf => skipping = false,
+            _ if skipping => {}
+            Token::Text(text) => output.push_str(text),
+            Token::Variable(name) => output.push_str(&alert.value(name)),
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_templates() {
+        let templates = Templates::default();
+        assert_eq!(
+            templates.render(&AlertContext::new("https://example.com", 0), Language::En),
+            "Site: https://example.com\n\nHoppla! We faced an error trying to reach the site! 🤒"
+        );
+        assert_eq!(
+            templates.render(&AlertContext::new("https://example.com", 503), Language::En),
+            "Site: https://example.com\n\nSchade! It's down or inaccessible to me! 😟\n\nStatus: <a href='https://httpstatuses.com/503'>503</a>"
+        );
+        assert_eq!(
+            templates.render(&AlertContext::new("https://example.com", 302), Language::En),
+            "Site: https://example.com\n\nSomething is fishy! 🐟"
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        let mut templates = Templates::default();
+        templates
+            .set(
+                StatusClass::ServerError,
+                Some("<b>{{site}}</b> returned {{status}}{{#if old_status}} after {{old_status}}{{/if}}{{#if duration}}, down for {{duration}}{{/if}}"),
+            )
+            .unwrap();
+        let alert = AlertContext {
+            site: "https://example.com/?a=1&b=2".to_string(),
+            status: 503,
+            old_status: Some(200),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            templates.render(&alert, Language::En),
+            "<b>https://example.com/?a=1&amp;b=2</b> returned 503 after 200"
+        );
+        // Other classes keep the built-in template
+        assert!(
+            templates
+                .render(&AlertContext::new("a.com", 200), Language::En)
+                .contains("Joohoo!")
+        );
+
+        templates.set(StatusClass::ServerError, None).unwrap();
+        assert!(templates.is_empty());
+    }
+
+    #[test]
+    fn test_builtin_templates_are_valid() {
+        for language in Language::ALL {
+            for class in StatusClass::ALL {
+                assert!(
+                    Templates::default()
+                        .set(class, Some(class.default_template(language)))
+                        .is_ok()
+                );
+            }
+        }
+        assert!(
+            Templates::default()
+                .render(&AlertContext::new("a.com", 503), Language::De)
+                .starts_with("Seite: a.com")
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let mut templates = Templates::default();
+        for template in [
+            "{{website}}",
+            "{{site",
+            "{{#if site}}",
+            "{{/if}}",
+            "{{#if site}}{{#if status}}{{/if}}{{/if}}",
+            "<b>{{site}}",
+            "<b>{{site}}</i>",
+            "{{#if duration}}<b>{{/if}}{{site}}</b>",
+            "<blink>{{site}}</blink>",
+            "{{site}} & {{status}}",
+            "{{site}} < 500",
+        ] {
+            assert!(
+                templates.set(StatusClass::Up, Some(template)).is_err(),
+                "{template}"
+            );
+        }
+        assert!(templates.is_empty());
+    }
+
+    #[test]
+    fn test_from_website() {
+        let now = Utc::now();
+        let website = Website {
+            url: "https://example.com".to_string(),
+            previous_status: Some(503),
+            status: 200,
+            outage: Some(chrono::Duration::minutes(90)),
+            ..Default::default()
+        };
+        let alert = AlertContext::from_website(&website, now, Language::En);
+        assert_eq!(alert.old_status, Some(503));
+        assert_eq!(alert.duration.as_deref(), Some("1h 30m"));
+
+        let website = Website {
+            status: 0,
+            failure: Some(crate::http::FailureKind::Dns),
+            ..website
+        };
+        let alert = AlertContext::from_website(&website, now, Language::De);
+        assert_eq!(
+            alert.failure.as_deref(),
+            Some("DNS-Auflösung fehlgeschlagen")
+        );
+    }
+
+    #[test]
+    fn test_class_names() {
+        for class in StatusClass::ALL {
+            assert_eq!(StatusClass::parse(class.name()), Some(class));
+        }
+        assert_eq!(StatusClass::parse("5XX"), Some(StatusClass::ServerError));
+        assert_eq!(StatusClass::parse("down"), None);
+    }
+}
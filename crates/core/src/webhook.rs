@@ -0,0 +1,436 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, KeyInit, Mac};
+use mongodb::Collection;
+use mongodb::bson::Document;
+use reqwest::{Client, StatusCode, header::CONTENT_TYPE, redirect::Policy};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use crate::http::FailureKind;
+use crate::incident::Transition;
+use crate::mongo::{ChatSettings, Website, log_webhook_delivery};
+use crate::notify::{Event, Notifier};
+
+pub const SIGNATURE_HEADER: &str = "X-ManDown-Signature";
+/// Unix time the request was signed at, part of the signed content
+pub const TIMESTAMP_HEADER: &str = "X-ManDown-Timestamp";
+pub const MAX_WEBHOOKS: usize = 5;
+const MAX_ATTEMPTS: u32 = 3;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An endpoint that receives a signed JSON POST for every status change of
+/// the chat's sites.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebhookTarget {
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature in the `X-ManDown-Signature` header
+    pub secret: String,
+}
+
+impl WebhookTarget {
+    pub fn new(url: &str) -> Self {
+        WebhookTarget {
+            url: url.to_string(),
+            secret: hex::encode(rand::random::<[u8; 16]>()),
+        }
+    }
+}
+
+/// An absolute http(s) URL with a host.
+pub fn is_http_url(input: &str) -> bool {
+    url::Url::parse(input)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+}
+
+/// Only absolute http(s) URLs whose host resolves to public addresses can
+/// receive webhooks, so a chat cannot make the bot post into the network it
+/// runs in.
+pub async fn is_valid_webhook_url(input: &str) -> bool {
+    public_addrs(input).await.is_some()
+}
+
+// The host's addresses, if it has some and all of them are public
+async fn public_addrs(input: &str) -> Option<(String, Vec<SocketAddr>)> {
+    if !is_http_url(input) {
+        return None;
+    }
+    let url = url::Url::parse(input).ok()?;
+    let host = url.host_str()?.trim_matches(['[', ']']).to_string();
+    let port = url.port_or_known_default()?;
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .ok()?
+        .collect();
+    let public = !addrs.is_empty() && addrs.iter().all(|addr| is_public(addr.ip()));
+    public.then_some((host, addrs))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    // Link-local, fe80::/10
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// A client that only connects to the addresses checked here, so the host
+/// cannot resolve to an internal one by the time of the request, and that
+/// does not follow redirects there either.
+pub(crate) async fn pinned_client(url: &str) -> Option<Client> {
+    let (host, addrs) = public_addrs(url).await?;
+    Client::builder()
+        .resolve_to_addrs(&host, &addrs)
+        .redirect(Policy::none())
+        .timeout(TIMEOUT)
+        .build()
+        .ok()
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WebhookPayload {
+    pub site: String,
+    pub old_status: Option<i32>,
+    pub new_status: i32,
+    pub transition: Transition,
+    pub failure: Option<FailureKind>,
+    /// RFC 3339 timestamps
+    pub changed_at: String,
+    pub incident_started_at: Option<String>,
+    pub chat_id: i64,
+}
+
+impl From<&Website> for WebhookPayload {
+    fn from(website: &Website) -> Self {
+        WebhookPayload {
+            site: website.url.clone(),
+            old_status: website.previous_status,
+            new_status: website.status,
+            transition: website.transition(),
+            failure: website.failure,
+            changed_at: website.last_updated.to_rfc3339(),
+            incident_started_at: website
+                .incident
+                .as_ref()
+                .map(|incident| incident.started_at.to_rfc3339()),
+            chat_id: website.chat_id,
+        }
+    }
+}
+
+/// The content that is signed: the timestamp, a dot and the body, so a
+/// captured request cannot be replayed later with a fresh timestamp.
+pub fn signed_content(timestamp: i64, body: &[u8]) -> Vec<u8> {
+    let mut content = format!("{timestamp}.").into_bytes();
+    content.extend_from_slice(body);
+    content
+}
+
+/// HMAC-SHA256 signature, as sent in the `X-ManDown-Signature` header.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Outcome of delivering one payload, kept in the `webhook_deliveries`
+/// collection.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Delivery {
+    pub chat_id: i64,
+    pub url: String,
+    pub site: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub at: DateTime<Utc>,
+}
+
+impl Delivery {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+// Server errors and rate limiting are worth another try, other statuses
+// will not change by retrying
+fn should_retry(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(500 << attempt)
+}
+
+/// POST a payload to one target, retrying with backoff.
+pub async fn deliver(
+    client: &Client,
+    target: &WebhookTarget,
+    payload: &WebhookPayload,
+) -> Delivery {
+    let body = serde_json::to_vec(payload).expect("payload serializes to JSON");
+    let mut delivery = Delivery {
+        chat_id: payload.chat_id,
+        url: target.url.clone(),
+        site: payload.site.clone(),
+        status: None,
+        attempts: 0,
+        error: None,
+        at: Utc::now(),
+    };
+
+    loop {
+        delivery.attempts += 1;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&target.secret, &signed_content(timestamp, &body));
+        let response = client
+            .post(&target.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+
+        let retry = match response {
+            Ok(response) => {
+                let status = response.status();
+                delivery.status = Some(status.as_u16());
+                if status.is_success() {
+                    delivery.error = None;
+                    return delivery;
+                }
+                delivery.error = Some(format!("HTTP {status}"));
+                should_retry(status)
+            }
+            Err(e) => {
+                delivery.status = None;
+                delivery.error = Some(FailureKind::from(&e).to_string());
+                true
+            }
+        };
+
+        if !retry || delivery.attempts >= MAX_ATTEMPTS {
+            return delivery;
+        }
+        tokio::time::sleep(backoff(delivery.attempts - 1)).await;
+    }
+}
+
+/// Posts every status change to the webhooks the chat registered, and logs
+/// the outcome of each delivery.
+pub struct WebhookNotifier {
+    collection: Collection<Document>,
+}
+
+impl WebhookNotifier {
+    pub fn new(collection: Collection<Document>) -> Self {
+        WebhookNotifier { collection }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, chat: &ChatSettings, events: &[Event]) -> bool {
+        let mut delivered = true;
+        for event in events {
+            let Event::Changed(website) = event else {
+                continue;
+            };
+            let payload = WebhookPayload::from(website);
+            for target in &chat.webhooks {
+                // Checked again on every delivery, as DNS may have changed
+                // since the webhook was added
+                let delivery = match pinned_client(&target.url).await {
+                    Some(client) => deliver(&client, target, &payload).await,
+                    None => Delivery {
+                        chat_id: payload.chat_id,
+                        url: target.url.clone(),
+                        site: payload.site.clone(),
+                        status: None,
+                        attempts: 0,
+                        error: Some("host does not resolve to a public address".to_string()),
+                        at: Utc::now(),
+                    },
+                };
+                if !delivery.succeeded() {
+                    log::info!(
+                        "Webhook delivery to {} failed after {} attempts",
+                        target.url,
+                        delivery.attempts
+                    );
+                    delivered = false;
+                }
+                if let Err(e) = log_webhook_delivery(&self.collection, &delivery).await {
+                    log::error!("Error logging webhook delivery: {e}");
+                }
+            }
+        }
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Answer each request with the next status in line, recording requests
+    async fn serve(statuses: &'static [&'static str]) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        tokio::spawn(async move {
+            for status in statuses {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut buf = [0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                log.lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&buf[..n]).to_string());
+                let response =
+                    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (format!("http://{addr}/hook"), requests)
+    }
+
+    fn payload() -> WebhookPayload {
+        WebhookPayload {
+            site: "https://example.com".to_string(),
+            old_status: Some(200),
+            new_status: 0,
+            transition: Transition::Down,
+            failure: Some(FailureKind::Timeout),
+            changed_at: "2024-06-01T12:00:00+00:00".to_string(),
+            incident_started_at: Some("2024-06-01T12:00:00+00:00".to_string()),
+            chat_id: 42,
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_payload_json() {
+        let json = serde_json::to_value(payload()).unwrap();
+        assert_eq!(json["transition"], "down");
+        assert_eq!(json["failure"], "timeout");
+        assert_eq!(json["old_status"], 200);
+        assert_eq!(json["chat_id"], 42);
+    }
+
+    #[tokio::test]
+    async fn test_valid_url() {
+        assert!(is_valid_webhook_url("https://93.184.216.34/mandown").await);
+        assert!(is_valid_webhook_url("http://[2606:2800:220:1::]:8080/hook").await);
+        assert!(!is_valid_webhook_url("ftp://93.184.216.34").await);
+        assert!(!is_valid_webhook_url("example.com/hook").await);
+        assert!(is_http_url("http://localhost:8080/hook"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_internal_addresses() {
+        for url in [
+            "http://localhost:8080/hook",
+            "http://127.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.1/hook",
+            "http://172.16.5.4/hook",
+            "http://192.168.1.1/hook",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(!is_valid_webhook_url(url).await, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_signed_content() {
+        assert_eq!(signed_content(1717243200, b"{}"), b"1717243200.{}");
+    }
+
+    #[tokio::test]
+    async fn test_deliver_signed() {
+        let (url, requests) = serve(&["204 No Content"]).await;
+        let target = WebhookTarget::new(&url);
+        let delivery = deliver(&Client::new(), &target, &payload()).await;
+
+        assert!(delivery.succeeded());
+        assert_eq!((delivery.status, delivery.attempts), (Some(204), 1));
+        let request = requests.lock().unwrap()[0].to_lowercase();
+        let timestamp: i64 = request
+            .lines()
+            .find_map(|line| line.strip_prefix("x-mandown-timestamp: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((Utc::now().timestamp() - timestamp).abs() < 60);
+        let body = serde_json::to_vec(&payload()).unwrap();
+        let signature = sign(&target.secret, &signed_content(timestamp, &body));
+        assert!(request.contains(&format!("x-mandown-signature: {signature}")));
+    }
+
+    #[tokio::test]
+    async fn test_deliver_retries_server_errors() {
+        let (url, requests) = serve(&["503 Service Unavailable", "200 OK"]).await;
+        let delivery = deliver(&Client::new(), &WebhookTarget::new(&url), &payload()).await;
+
+        assert!(delivery.succeeded());
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_gives_up_on_client_errors() {
+        let (url, _) = serve(&["404 Not Found"]).await;
+        let delivery = deliver(&Client::new(), &WebhookTarget::new(&url), &payload()).await;
+
+        assert!(!delivery.succeeded());
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.error.as_deref(), Some("HTTP 404 Not Found"));
+    }
+}
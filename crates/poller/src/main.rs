@@ -1,7 +1,9 @@
 use dotenvy::dotenv;
 use man_down_core::config::init_logger;
+use man_down_core::email::SmtpConfig;
 use man_down_core::http::cust_client;
 use man_down_core::mongo::init_mongo;
+use man_down_core::notify::Notifiers;
 use man_down_core::poll::run_once;
 use teloxide::prelude::*;
 
@@ -9,10 +11,13 @@ use teloxide::prelude::*;
 async fn main() {
     dotenv().ok();
     init_logger();
+    SmtpConfig::from_env().expect("Invalid SMTP settings");
 
     let collection = init_mongo().await;
     let http_client = cust_client(30);
     let bot = Bot::from_env();
 
-    run_once(&collection, bot, http_client).await;
+    let notifiers = Notifiers::standard(bot, &collection, http_client.clone());
+
+    run_once(&collection, &notifiers, http_client).await;
 }
@@ -1,6 +1,7 @@
 use dotenvy::dotenv;
 use man_down_core::command::start_command_with_listener;
 use man_down_core::config::init_logger;
+use man_down_core::email::SmtpConfig;
 use man_down_core::http::cust_client;
 use man_down_core::mongo::init_mongo;
 use teloxide::prelude::*;
@@ -11,6 +12,7 @@ use url::Url;
 async fn main() {
     dotenv().ok();
     init_logger();
+    SmtpConfig::from_env().expect("Invalid SMTP settings");
 
     let collection = init_mongo().await;
     let http_client = cust_client(30);